@@ -0,0 +1,28 @@
+//! Decode arbitrary bytes into a `serde_json::Value`, then check that
+//! encoding and decoding the value again gives back the same value.
+//!
+//! Run with `cargo fuzz run roundtrip`. Inputs that made this target fail
+//! are kept as regression tests in `tests/fuzz_regressions.rs`.
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use serde::Deserialize;
+use serde_json::Value;
+use serde_sqlite_jsonb::Deserializer;
+
+fuzz_target!(|data: &[u8]| {
+    // deeply nested input would overflow the stack without a limit
+    let mut de = Deserializer::from_bytes(data).with_max_depth(128);
+    let value = match Value::deserialize(&mut de).and_then(|v| {
+        de.end()?;
+        Ok(v)
+    }) {
+        Ok(value) => value,
+        Err(_) => return,
+    };
+    let encoded = serde_sqlite_jsonb::to_vec(&value)
+        .expect("a decoded value can be encoded");
+    let decoded: Value = serde_sqlite_jsonb::from_slice(&encoded)
+        .expect("an encoded value can be decoded");
+    assert_eq!(value, decoded);
+});
@@ -0,0 +1,88 @@
+//! Binary data stored as base64 text, behind the `base64` feature.
+
+use ::base64::engine::general_purpose::STANDARD;
+use ::base64::Engine;
+use serde::de::{self, Deserialize, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
+use std::fmt;
+
+/// Append the standard, padded base64 encoding of `bytes` to `out`.
+pub(crate) fn encode_into(bytes: &[u8], out: &mut Vec<u8>) {
+    out.extend_from_slice(STANDARD.encode(bytes).as_bytes());
+}
+
+/// Decode standard, padded base64 text.
+pub(crate) fn decode(text: &[u8]) -> crate::Result<Vec<u8>> {
+    STANDARD
+        .decode(text)
+        .map_err(|e| crate::Error::Message(format!("invalid base64 text: {e}")))
+}
+
+/// Binary data stored as base64 inside a JSONB text element.
+///
+/// JSONB has no binary type, so a common way to store blobs in it is to
+/// encode them as base64 strings. This type decodes such a string into its
+/// raw bytes, and encodes the bytes back as base64 text when serialized.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Base64(pub Vec<u8>);
+
+impl From<Vec<u8>> for Base64 {
+    fn from(bytes: Vec<u8>) -> Self {
+        Base64(bytes)
+    }
+}
+
+impl From<Base64> for Vec<u8> {
+    fn from(b: Base64) -> Self {
+        b.0
+    }
+}
+
+impl Serialize for Base64 {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(&self.0))
+    }
+}
+
+struct Base64Visitor;
+
+impl<'de> Visitor<'de> for Base64Visitor {
+    type Value = Base64;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a base64 string")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Base64, E> {
+        STANDARD.decode(v).map(Base64).map_err(E::custom)
+    }
+}
+
+impl<'de> Deserialize<'de> for Base64 {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(Base64Visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_base64_roundtrip() {
+        let bytes = Base64(vec![0, 159, 146, 150, 255]);
+        let encoded = crate::to_vec(&bytes).unwrap();
+        assert_eq!(encoded, b"\x8aAJ+Slv8=");
+        assert_eq!(crate::from_slice::<Base64>(&encoded).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_base64_invalid() {
+        assert!(crate::from_slice::<Base64>(b"\x17!").is_err());
+    }
+}
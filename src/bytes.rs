@@ -0,0 +1,17 @@
+//! Storage of binary data, for which JSONB has no dedicated type.
+
+/// How byte strings (such as `serde_bytes` fields) are written by a
+/// [`Serializer`](crate::Serializer).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BytesEncoding {
+    /// An array of integers between 0 and 255, which any
+    /// [`Deserializer`](crate::Deserializer) decodes back into bytes.
+    #[default]
+    Array,
+    /// A `Text` element containing the standard, padded base64 encoding of
+    /// the bytes, decoded back into bytes by a
+    /// [`Deserializer`](crate::Deserializer) built with
+    /// [`with_base64_bytes(true)`](crate::Deserializer::with_base64_bytes).
+    #[cfg(feature = "base64")]
+    Base64,
+}
@@ -0,0 +1,241 @@
+//! Re-encoding of JSONB data with the smallest possible headers.
+//!
+//! The JSONB format allows a payload size to be stored in a header that is
+//! larger than needed. SQLite itself sometimes reserves large headers when
+//! it builds a value in place. The canonical encoding of a value uses the
+//! smallest header for every element, and keeps the payloads of scalars
+//! unchanged.
+
+use crate::error::Result;
+use crate::header::{Header, HeaderWidth};
+use crate::raw::{walk, RawElement};
+use std::io::Write;
+
+/// Compute the canonical payload size of every array and object in `root`,
+/// in the order in which they appear, along with the canonical length of
+/// `root` itself.
+fn canonical_sizes(root: RawElement) -> Result<(Vec<usize>, usize)> {
+    encoded_sizes(root, HeaderWidth::minimal)
+}
+
+/// Like [`canonical_sizes`], for an encoding that gives the header of an
+/// element with a payload of `n` bytes the width `width_for(n)`.
+fn encoded_sizes(
+    root: RawElement,
+    width_for: impl Fn(usize) -> HeaderWidth,
+) -> Result<(Vec<usize>, usize)> {
+    // number of bytes an element occupies
+    let element_len =
+        |payload_size| width_for(payload_size).header_len() + payload_size;
+    let mut sizes = Vec::new();
+    if !root.is_container() {
+        return Ok((sizes, element_len(root.payload().len())));
+    }
+    // containers being measured, with their index in `sizes`
+    let mut stack = vec![(root.children(), 0)];
+    sizes.push(0);
+    loop {
+        let (children, index) = stack.last_mut().expect("root is on the stack");
+        let len = match children.next().transpose()? {
+            Some(child) if child.is_container() => {
+                stack.push((child.children(), sizes.len()));
+                sizes.push(0);
+                continue;
+            }
+            Some(child) => element_len(child.payload().len()),
+            None => {
+                let index = *index;
+                stack.pop();
+                element_len(sizes[index])
+            }
+        };
+        match stack.last() {
+            Some(&(_, parent)) => sizes[parent] += len,
+            None => return Ok((sizes, len)),
+        }
+    }
+}
+
+/// Compute the length, in bytes, of the canonical encoding of a JSONB value,
+/// without producing it.
+///
+/// `bytes.len() - canonical_len(bytes)?` is the number of bytes that
+/// [`canonicalize`] would save.
+pub fn canonical_len(bytes: &[u8]) -> Result<usize> {
+    let (_, len) = canonical_sizes(RawElement::read_root(bytes)?)?;
+    Ok(len)
+}
+
+/// Re-encode a JSONB value so that every element uses the smallest
+/// possible header. Payloads are copied unchanged.
+pub fn canonicalize(bytes: &[u8]) -> Result<Vec<u8>> {
+    // the canonical encoding is never longer than the original
+    let mut out = Vec::with_capacity(bytes.len());
+    canonicalize_to(bytes, &mut out)?;
+    Ok(out)
+}
+
+/// Like [`canonicalize`], but write the canonical encoding to `out` as it is
+/// produced, instead of building it in memory.
+///
+/// The sizes of all arrays and objects are computed before anything is
+/// written, so nothing is written if `bytes` is not valid JSONB.
+pub fn canonicalize_to<W: Write>(bytes: &[u8], out: &mut W) -> Result<()> {
+    write_with_widths(bytes, out, HeaderWidth::minimal)
+}
+
+/// Re-encode a JSONB value with headers of the given width everywhere,
+/// rather than the smallest ones. Payloads are copied unchanged.
+///
+/// This produces any of the encodings the format allows, for instance to
+/// compare with other encoders. Fails with [`Error::HeaderTooNarrow`] if
+/// the width cannot hold the payload size of one of the elements.
+///
+/// [`Error::HeaderTooNarrow`]: crate::Error::HeaderTooNarrow
+pub fn reencode_with_width(
+    bytes: &[u8],
+    width: HeaderWidth,
+) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    write_with_widths(bytes, &mut out, |_| width)?;
+    Ok(out)
+}
+
+/// Write `bytes` to `out`, giving the header of an element with a payload
+/// of `n` bytes the width `width_for(n)`.
+fn write_with_widths<W: Write>(
+    bytes: &[u8],
+    out: &mut W,
+    width_for: impl Fn(usize) -> HeaderWidth,
+) -> Result<()> {
+    let (sizes, _) = encoded_sizes(RawElement::read_root(bytes)?, &width_for)?;
+    // the widths of containers are checked before anything is written,
+    // and those of scalars as they are written
+    let too_narrow = sizes
+        .iter()
+        .copied()
+        .find(|&size| !width_for(size).can_hold(size));
+    if let Some(payload_size) = too_narrow {
+        return Err(crate::Error::HeaderTooNarrow {
+            width: width_for(payload_size),
+            payload_size,
+        });
+    }
+    let mut sizes = sizes.into_iter();
+    walk(bytes, |element, _depth| {
+        let payload_size = if element.is_container() {
+            sizes.next().expect("one size per container")
+        } else {
+            element.payload().len()
+        };
+        let header = Header {
+            element_type: element.element_type(),
+            payload_size,
+        };
+        let (head, head_len) =
+            header.to_bytes_with_width(width_for(payload_size))?;
+        out.write_all(&head[..head_len])?;
+        if !element.is_container() {
+            out.write_all(element.payload())?;
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    // [1, {"a": "xy"}], with nine-byte headers everywhere
+    const PADDED: &[u8] = b"\
+        \xfb\x00\x00\x00\x00\x00\x00\x00\x28\
+        \xf3\x00\x00\x00\x00\x00\x00\x00\x011\
+        \xfc\x00\x00\x00\x00\x00\x00\x00\x15\
+        \xf7\x00\x00\x00\x00\x00\x00\x00\x01a\
+        \xf7\x00\x00\x00\x00\x00\x00\x00\x02xy";
+
+    #[test]
+    fn test_canonicalize() {
+        assert_eq!(canonicalize(PADDED).unwrap(), b"\x8b\x131\x5c\x17a\x27xy");
+    }
+
+    #[test]
+    fn test_canonical_len_matches_canonicalize() {
+        for bytes in [PADDED, b"\x131", b"\x0b", b"\xc7\x0c123456789012"] {
+            let canonical = canonicalize(bytes).unwrap();
+            assert_eq!(canonical_len(bytes).unwrap(), canonical.len());
+            assert_eq!(canonicalize(&canonical).unwrap(), canonical);
+        }
+    }
+
+    #[test]
+    fn test_canonicalize_keeps_value() {
+        let value = (vec!["x".repeat(300)], 1.5, Some(true));
+        let canonical = canonicalize(&crate::to_vec(&value).unwrap()).unwrap();
+        assert_eq!(canonical, crate::to_vec(&value).unwrap());
+        assert_eq!(
+            crate::from_slice::<(Vec<String>, f64, Option<bool>)>(&canonical),
+            Ok(value)
+        );
+    }
+
+    #[test]
+    fn test_canonicalize_invalid() {
+        assert_eq!(canonicalize(b""), Err(Error::Empty));
+        assert_eq!(
+            canonical_len(b"\x2b\x57h"),
+            Err(Error::ElementExceedsContainer)
+        );
+    }
+
+    #[test]
+    fn test_canonicalize_to() {
+        let mut out = Vec::new();
+        canonicalize_to(PADDED, &mut out).unwrap();
+        assert_eq!(out, canonicalize(PADDED).unwrap());
+        // invalid input is detected before anything is written
+        let mut out = Vec::new();
+        assert!(canonicalize_to(b"\x2b\x57h", &mut out).is_err());
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn test_reencode_with_width() {
+        let encodings: [(HeaderWidth, &[u8]); 5] = [
+            (HeaderWidth::Inline, b"\x131"),
+            (HeaderWidth::U8, b"\xc3\x011"),
+            (HeaderWidth::U16, b"\xd3\x00\x011"),
+            (HeaderWidth::U32, b"\xe3\x00\x00\x00\x011"),
+            (HeaderWidth::U64, b"\xf3\x00\x00\x00\x00\x00\x00\x00\x011"),
+        ];
+        for (width, expected) in encodings {
+            let encoded = reencode_with_width(b"\x131", width).unwrap();
+            assert_eq!(encoded, expected, "{width:?}");
+            assert_eq!(crate::from_slice::<u8>(&encoded), Ok(1));
+            assert_eq!(canonicalize(&encoded).unwrap(), b"\x131");
+        }
+    }
+
+    #[test]
+    fn test_reencode_with_width_nested() {
+        let encoded = reencode_with_width(PADDED, HeaderWidth::U8).unwrap();
+        assert_eq!(encoded, b"\xcb\x0c\xc3\x011\xcc\x07\xc7\x01a\xc7\x02xy");
+        assert_eq!(
+            reencode_with_width(&encoded, HeaderWidth::U64).unwrap(),
+            PADDED
+        );
+        assert_eq!(
+            reencode_with_width(PADDED, HeaderWidth::Inline).unwrap(),
+            b"\x8b\x131\x5c\x17a\x27xy"
+        );
+        let long_text = b"\xc7\x0c123456789012";
+        assert_eq!(
+            reencode_with_width(long_text, HeaderWidth::Inline),
+            Err(Error::HeaderTooNarrow {
+                width: HeaderWidth::Inline,
+                payload_size: 12,
+            })
+        );
+    }
+}
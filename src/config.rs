@@ -0,0 +1,261 @@
+//! Options of the [`Deserializer`], gathered to be reused across decodes.
+
+use crate::de::{NumberHook, OnReserved, ParsedNumber};
+use crate::error::Result;
+use crate::header::ElementType;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+#[cfg(doc)]
+use crate::Deserializer;
+
+/// A set of [`Deserializer`] options, built once and applied to any number
+/// of deserializers with [`Deserializer::with_config`] or
+/// [`from_bytes_with_config`](crate::from_bytes_with_config).
+///
+/// Each option is set by the method with the same name as the
+/// [`Deserializer`] method that sets it on a single deserializer, and has
+/// the same default. Observers, which keep state across calls, and
+/// cancellation flags, which belong to a single decode, are not part of the
+/// configuration.
+///
+/// ```
+/// use serde_sqlite_jsonb::{from_bytes_with_config, DeserializerConfig};
+///
+/// let config = DeserializerConfig::new()
+///     .with_max_depth(8)
+///     .with_lenient_numbers(true);
+/// let n: u8 = from_bytes_with_config(b"\x33 1 ", &config).unwrap();
+/// assert_eq!(n, 1);
+/// ```
+#[derive(Clone)]
+pub struct DeserializerConfig {
+    pub(crate) max_elements: Option<usize>,
+    pub(crate) max_input_bytes: Option<usize>,
+    pub(crate) max_string_bytes: Option<usize>,
+    pub(crate) max_depth: Option<usize>,
+    pub(crate) number_hook: Option<Arc<NumberHook>>,
+    pub(crate) human_readable: bool,
+    pub(crate) lenient_numbers: bool,
+    pub(crate) coerce_int_float: bool,
+    pub(crate) lenient_text: bool,
+    pub(crate) lossy_utf8: bool,
+    #[cfg(feature = "base64")]
+    pub(crate) base64_bytes: bool,
+    pub(crate) strict_empty_payloads: bool,
+    pub(crate) allow_trailing_zeros: bool,
+    pub(crate) allow_non_finite: bool,
+    pub(crate) lenient_bool: bool,
+    pub(crate) on_reserved: OnReserved,
+    pub(crate) key_renames: Option<Arc<HashMap<String, String>>>,
+}
+
+impl Default for DeserializerConfig {
+    fn default() -> Self {
+        DeserializerConfig {
+            max_elements: None,
+            max_input_bytes: None,
+            max_string_bytes: None,
+            max_depth: None,
+            number_hook: None,
+            human_readable: true,
+            lenient_numbers: false,
+            coerce_int_float: false,
+            lenient_text: false,
+            lossy_utf8: false,
+            #[cfg(feature = "base64")]
+            base64_bytes: false,
+            strict_empty_payloads: false,
+            allow_trailing_zeros: false,
+            allow_non_finite: false,
+            lenient_bool: false,
+            on_reserved: OnReserved::Error,
+            key_renames: None,
+        }
+    }
+}
+
+impl fmt::Debug for DeserializerConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut s = f.debug_struct("DeserializerConfig");
+        s.field("max_elements", &self.max_elements)
+            .field("max_input_bytes", &self.max_input_bytes)
+            .field("max_string_bytes", &self.max_string_bytes)
+            .field("max_depth", &self.max_depth)
+            .field("number_hook", &self.number_hook.is_some())
+            .field("human_readable", &self.human_readable)
+            .field("lenient_numbers", &self.lenient_numbers)
+            .field("coerce_int_float", &self.coerce_int_float)
+            .field("lenient_text", &self.lenient_text)
+            .field("lossy_utf8", &self.lossy_utf8);
+        #[cfg(feature = "base64")]
+        s.field("base64_bytes", &self.base64_bytes);
+        s.field("strict_empty_payloads", &self.strict_empty_payloads)
+            .field("allow_trailing_zeros", &self.allow_trailing_zeros)
+            .field("allow_non_finite", &self.allow_non_finite)
+            .field("lenient_bool", &self.lenient_bool)
+            .field("on_reserved", &self.on_reserved)
+            .field("key_renames", &self.key_renames)
+            .finish()
+    }
+}
+
+impl DeserializerConfig {
+    /// A configuration with all options at their default.
+    pub fn new() -> Self {
+        DeserializerConfig::default()
+    }
+
+    /// See [`Deserializer::with_max_elements`].
+    pub fn with_max_elements(mut self, max: usize) -> Self {
+        self.max_elements = Some(max);
+        self
+    }
+
+    /// See [`Deserializer::with_max_input_bytes`].
+    pub fn with_max_input_bytes(mut self, max: usize) -> Self {
+        self.max_input_bytes = Some(max);
+        self
+    }
+
+    /// See [`Deserializer::with_max_total_string_bytes`].
+    pub fn with_max_total_string_bytes(mut self, max: usize) -> Self {
+        self.max_string_bytes = Some(max);
+        self
+    }
+
+    /// See [`Deserializer::with_max_depth`].
+    pub fn with_max_depth(mut self, max: usize) -> Self {
+        self.max_depth = Some(max);
+        self
+    }
+
+    /// See [`Deserializer::with_number_visitor`].
+    pub fn with_number_visitor(
+        mut self,
+        hook: impl Fn(&str, ElementType) -> Result<ParsedNumber>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        self.number_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// See [`Deserializer::with_human_readable`].
+    pub fn with_human_readable(mut self, human_readable: bool) -> Self {
+        self.human_readable = human_readable;
+        self
+    }
+
+    /// See [`Deserializer::with_lenient_numbers`].
+    pub fn with_lenient_numbers(mut self, lenient: bool) -> Self {
+        self.lenient_numbers = lenient;
+        self
+    }
+
+    /// See [`Deserializer::with_coerce_int_float`].
+    pub fn with_coerce_int_float(mut self, coerce: bool) -> Self {
+        self.coerce_int_float = coerce;
+        self
+    }
+
+    /// See [`Deserializer::with_lenient_text`].
+    pub fn with_lenient_text(mut self, lenient: bool) -> Self {
+        self.lenient_text = lenient;
+        self
+    }
+
+    /// See [`Deserializer::with_lossy_utf8`].
+    pub fn with_lossy_utf8(mut self, lossy: bool) -> Self {
+        self.lossy_utf8 = lossy;
+        self
+    }
+
+    /// See [`Deserializer::with_base64_bytes`].
+    #[cfg(feature = "base64")]
+    pub fn with_base64_bytes(mut self, base64: bool) -> Self {
+        self.base64_bytes = base64;
+        self
+    }
+
+    /// See [`Deserializer::with_strict_empty_payloads`].
+    pub fn with_strict_empty_payloads(mut self, strict: bool) -> Self {
+        self.strict_empty_payloads = strict;
+        self
+    }
+
+    /// See [`Deserializer::with_allow_trailing_zeros`].
+    pub fn with_allow_trailing_zeros(mut self, allow: bool) -> Self {
+        self.allow_trailing_zeros = allow;
+        self
+    }
+
+    /// See [`Deserializer::with_allow_non_finite`].
+    pub fn with_allow_non_finite(mut self, allow: bool) -> Self {
+        self.allow_non_finite = allow;
+        self
+    }
+
+    /// See [`Deserializer::with_lenient_bool`].
+    pub fn with_lenient_bool(mut self, lenient: bool) -> Self {
+        self.lenient_bool = lenient;
+        self
+    }
+
+    /// See [`Deserializer::with_on_reserved`].
+    pub fn with_on_reserved(mut self, on_reserved: OnReserved) -> Self {
+        self.on_reserved = on_reserved;
+        self
+    }
+
+    /// See [`Deserializer::with_key_rename`].
+    pub fn with_key_rename(mut self, renames: HashMap<String, String>) -> Self {
+        self.key_renames = Some(Arc::new(renames));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{from_bytes_with_config, Deserializer, Error};
+    use serde::Deserialize;
+
+    #[test]
+    fn test_config_applies_options() {
+        #[derive(Debug, PartialEq, serde_derive::Deserialize)]
+        struct Item {
+            id: u8,
+        }
+        let config = DeserializerConfig::new()
+            .with_lenient_numbers(true)
+            .with_allow_trailing_zeros(true)
+            .with_key_rename(HashMap::from([("ID".into(), "id".into())]));
+        // {"ID": " 7"}, padded with zeros
+        let bytes = b"\x6c\x27ID\x23 7\x00\x00";
+        for _ in 0..2 {
+            let item: Item = from_bytes_with_config(bytes, &config).unwrap();
+            assert_eq!(item, Item { id: 7 });
+        }
+        assert!(crate::from_slice::<Item>(bytes).is_err());
+    }
+
+    #[test]
+    fn test_config_limits() {
+        let config = DeserializerConfig::new().with_max_depth(1);
+        // [[1]]
+        let nested = b"\x3b\x2b\x131";
+        assert_eq!(
+            from_bytes_with_config::<Vec<Vec<u8>>>(nested, &config),
+            Err(Error::DepthLimitExceeded(1))
+        );
+        let config = config.with_max_depth(2).with_max_input_bytes(2);
+        let mut de = Deserializer::with_config(nested, &config);
+        assert_eq!(
+            Vec::<Vec<u8>>::deserialize(&mut de),
+            Err(Error::InputTooLarge(2))
+        );
+    }
+}
@@ -6,22 +6,215 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use crate::config::DeserializerConfig;
 use crate::error::{Error, Result};
 use crate::header::{ElementType, Header};
-use serde::de::{self, Deserialize, IntoDeserializer, SeqAccess, Visitor};
-use std::io::Read;
+use crate::limited_reader::LimitedCountingReader;
+use serde::de::{
+    self, Deserialize, DeserializeOwned, IntoDeserializer, SeqAccess, Visitor,
+};
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 /// A structure that deserializes SQLite JSONB data into Rust values.
 pub struct Deserializer<R: Read> {
     /// The reader that the deserializer reads from.
-    reader: R,
+    reader: LimitedCountingReader<R>,
+    /// Number of bytes consumed from the reader so far.
+    position: usize,
+    /// Position at which the innermost array or object being decoded ends.
+    container_end: Option<usize>,
+    /// A header that was read but not consumed yet.
+    peeked: Option<Header>,
+    /// Number of element headers read so far.
+    elements: usize,
+    /// Maximum number of element headers that may be read.
+    max_elements: Option<usize>,
+    /// Total size of the text payloads decoded so far.
+    string_bytes: usize,
+    /// Maximum total size of the text payloads that may be decoded.
+    max_string_bytes: Option<usize>,
+    /// Number of arrays and objects enclosing the element being decoded.
+    depth: usize,
+    /// Maximum number of arrays and objects that may be nested.
+    max_depth: Option<usize>,
+    /// Custom parser for numbers decoded through `deserialize_any`.
+    number_hook: Option<Arc<NumberHook>>,
+    /// Function called on every header that is read.
+    observer: Option<Box<Observer>>,
+    /// Flag that aborts decoding when it is set.
+    cancel: Option<Arc<AtomicBool>>,
+    /// The buffer that the input is read from, when it can be shared by
+    /// decoded values.
+    #[cfg(feature = "bytes")]
+    shared_input: Option<::bytes::Bytes>,
+    /// Value returned by `is_human_readable`.
+    human_readable: bool,
+    /// Whether whitespace around the payload of an `Int` is accepted.
+    lenient_numbers: bool,
+    /// Whether `Int` elements that contain a float are truncated, instead
+    /// of rejected.
+    coerce_int_float: bool,
+    /// Whether all text elements are decoded as `Text5`.
+    lenient_text: bool,
+    /// Whether invalid UTF-8 in `TextRaw` elements is replaced instead of
+    /// rejected.
+    lossy_utf8: bool,
+    /// Whether text decoded as bytes is base64 that needs decoding.
+    #[cfg(feature = "base64")]
+    base64_bytes: bool,
+    /// Whether `Null`, `True` and `False` elements with a payload are
+    /// rejected.
+    strict_empty_payloads: bool,
+    /// Whether zero bytes are accepted after the value, by [`Self::end`].
+    allow_trailing_zeros: bool,
+    /// Whether NaN and infinite floats are accepted.
+    allow_non_finite: bool,
+    /// Whether booleans are also decoded from integers and text.
+    lenient_bool: bool,
+    /// How reserved element types are decoded by `deserialize_any`.
+    on_reserved: OnReserved,
+    /// New names for object keys, applied before keys are visited.
+    key_renames: Option<Arc<HashMap<String, String>>>,
+    /// The bytes of the last header that was read, as they appear in the
+    /// input, and their length.
+    header_bytes: [u8; 9],
+    header_len: usize,
+    /// Buffer reused to read payloads that are parsed right away, such as
+    /// object keys and numbers, to avoid an allocation per element.
+    scratch: Vec<u8>,
+}
+
+/// How the payload of a text element is turned into a string.
+#[derive(Debug, Clone, Copy)]
+enum TextDecoding {
+    /// The payload is the string, with invalid UTF-8 replaced if `lossy`
+    Verbatim { lossy: bool },
+    /// The payload is the content of a JSON string literal
+    Json,
+    /// The payload is the content of a JSON5 string literal
+    Json5,
+}
+
+/// Payloads up to this size are read into the scratch buffer in one go.
+const SCRATCH_PREALLOCATION_LIMIT: usize = 4096;
+
+/// A function that parses the text of a number element.
+pub type NumberHook =
+    dyn Fn(&str, ElementType) -> Result<ParsedNumber> + Send + Sync;
+
+/// How `deserialize_any` treats elements of the types reserved for future
+/// expansion of the format, set with [`Deserializer::with_on_reserved`].
+#[derive(Clone, Copy, Default)]
+pub enum OnReserved {
+    /// Fail with [`Error::ReservedElementType`]
+    #[default]
+    Error,
+    /// Skip the payload, and visit the element as a null
+    SkipAsNull,
+    /// Decode, instead of the element, the JSONB value returned by the
+    /// function, which receives the type and payload of the element
+    Custom(fn(ElementType, &[u8]) -> Result<Vec<u8>>),
+}
+
+impl std::fmt::Debug for OnReserved {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            OnReserved::Error => "Error",
+            OnReserved::SkipAsNull => "SkipAsNull",
+            OnReserved::Custom(_) => "Custom",
+        })
+    }
+}
+
+/// A function called with every element header that is read, along with the
+/// byte offset at which the header starts.
+pub type Observer = dyn FnMut(&Header, usize) + Send;
+
+/// A number parsed by a [`NumberHook`], handed to the visitor of
+/// `deserialize_any`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParsedNumber {
+    /// Visited with `visit_u64`
+    U64(u64),
+    /// Visited with `visit_i64`
+    I64(i64),
+    /// Visited with `visit_f64`
+    F64(f64),
+    /// Visited with `visit_string`, for types that parse numbers themselves
+    String(String),
 }
 
 impl<'a> Deserializer<&'a [u8]> {
     /// Deserialize an instance of type `T` from a byte slice of SQLite JSONB data.
     #[allow(clippy::should_implement_trait)]
     pub fn from_bytes(input: &'a [u8]) -> Self {
-        Deserializer { reader: input }
+        let mut deserializer = Deserializer::new(input);
+        deserializer.reader.set_input_len(input.len());
+        deserializer
+    }
+
+    /// Deserialize SQLite JSONB data from a byte slice, with the options
+    /// of `config`.
+    pub fn with_config(input: &'a [u8], config: &DeserializerConfig) -> Self {
+        Deserializer::from_bytes(input).apply_config(config)
+    }
+
+    /// Deserialize SQLite JSONB data from a reference-counted buffer, which
+    /// the fields decoded with [`shared_text`](crate::shared_text) share
+    /// instead of copying their text.
+    #[cfg(feature = "bytes")]
+    pub fn from_bytes_buf(buf: &'a ::bytes::Bytes) -> Self {
+        let mut deserializer = Deserializer::from_bytes(buf);
+        deserializer.shared_input = Some(buf.clone());
+        deserializer
+    }
+
+    /// Deserialize SQLite JSONB data that starts at the given offset in a
+    /// byte slice. Byte positions are still counted from the start of the
+    /// slice.
+    ///
+    /// Fails with [`Error::UnexpectedEof`] if `offset` is past the end of
+    /// `input`.
+    pub fn from_bytes_at(input: &'a [u8], offset: usize) -> Result<Self> {
+        let input = input.get(offset..).ok_or(Error::UnexpectedEof)?;
+        let mut deserializer = Deserializer::from_bytes(input);
+        deserializer.position = offset;
+        Ok(deserializer)
+    }
+
+    /// Start decoding a new input, keeping the options of the deserializer
+    /// and the buffers it allocated, to decode many values without setting
+    /// up a new deserializer for each of them.
+    ///
+    /// All the state of the previous input is discarded, including the
+    /// counts checked against limits such as [`Self::with_max_elements`].
+    pub fn reset(&mut self, input: &'a [u8]) {
+        self.reader.reset(input);
+        self.reader.set_input_len(input.len());
+        self.position = 0;
+        self.container_end = None;
+        self.peeked = None;
+        self.elements = 0;
+        self.string_bytes = 0;
+        self.depth = 0;
+        #[cfg(feature = "bytes")]
+        {
+            self.shared_input = None;
+        }
+    }
+
+    /// Read the header of the next element, and return its type along with
+    /// its payload, borrowed from the input without copying it.
+    /// The deserializer moves past the whole element.
+    pub fn read_header_borrowed(&mut self) -> Result<(ElementType, &'a [u8])> {
+        let header = self.read_header()?;
+        self.check_available(header)?;
+        let payload = self.reader.split_to(header.payload_size)?;
+        self.position += header.payload_size;
+        Ok((header.element_type, payload))
     }
 }
 
@@ -32,37 +225,520 @@ where
 {
     let mut deserializer = Deserializer::from_bytes(s);
     let t = T::deserialize(&mut deserializer)?;
-    if deserializer.reader.is_empty() {
-        Ok(t)
-    } else {
-        Err(Error::TrailingCharacters)
+    deserializer.end()?;
+    Ok(t)
+}
+
+/// Deserialize an instance of type `T` from a reference-counted buffer of
+/// SQLite JSONB data, with the text of the fields decoded with
+/// [`shared_text`](crate::shared_text) sharing the buffer.
+#[cfg(feature = "bytes")]
+pub fn from_bytes_buf<T>(buf: ::bytes::Bytes) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    let mut deserializer = Deserializer::from_bytes_buf(&buf);
+    let t = T::deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(t)
+}
+
+/// Deserialize an instance of type `T` from a byte slice of SQLite JSONB data,
+/// with the options of `config`.
+pub fn from_bytes_with_config<'a, T>(
+    s: &'a [u8],
+    config: &DeserializerConfig,
+) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    let mut deserializer = Deserializer::with_config(s, config);
+    let t = T::deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(t)
+}
+
+impl Deserializer<std::io::Cursor<Vec<u8>>> {
+    /// Create a deserializer that takes ownership of a buffer of SQLite
+    /// JSONB data. Values that are deserialized from it must own their data.
+    pub fn from_vec(input: Vec<u8>) -> Self {
+        let len = input.len();
+        let mut deserializer = Deserializer::new(std::io::Cursor::new(input));
+        deserializer.reader.set_input_len(len);
+        deserializer
     }
 }
 
-/// Deserialize an instance of type `T` from a byte slice of SQLite JSONB data.
-pub fn from_reader<'a, R: Read, T>(reader: R) -> Result<T>
+/// Deserialize an instance of type `T` from an owned buffer of SQLite JSONB
+/// data.
+pub fn from_vec<T>(v: Vec<u8>) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    let mut deserializer = Deserializer::from_vec(v);
+    let t = T::deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(t)
+}
+
+/// Deserialize an instance of type `T` from a byte slice of SQLite JSONB data,
+/// and return it along with the element type of the root of the data.
+pub fn from_bytes_typed<'a, T>(s: &'a [u8]) -> Result<(ElementType, T)>
 where
     T: Deserialize<'a>,
 {
-    let mut deserializer = Deserializer { reader };
+    let mut deserializer = Deserializer::from_bytes(s);
+    let header = deserializer.read_header()?;
+    deserializer.peeked = Some(header);
     let t = T::deserialize(&mut deserializer)?;
-    let Deserializer { mut reader } = deserializer;
-    if reader.read(&mut [0])? == 0 {
-        Ok(t)
-    } else {
-        Err(Error::TrailingCharacters)
+    deserializer.end()?;
+    Ok((header.element_type, t))
+}
+
+/// Return the element type of the root of SQLite JSONB data, reading only
+/// its header. The payload is neither read nor validated, and reserved
+/// element types are returned like the others.
+pub fn root_type(s: &[u8]) -> Result<ElementType> {
+    let header = Deserializer::from_bytes(s).read_header_checked(true)?;
+    Ok(header.element_type)
+}
+
+/// Deserialize an instance of type `T` from a reader of SQLite JSONB data.
+///
+/// The data is streamed, so `T` cannot borrow from it.
+pub fn from_reader<R: Read, T>(reader: R) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    let mut deserializer = Deserializer::new(reader);
+    let t = T::deserialize(&mut deserializer)?;
+    deserializer.end()?;
+    Ok(t)
+}
+
+/// Deserialize a single element from a reader of SQLite JSONB data, and
+/// return the reader along with it, positioned just after the element.
+///
+/// Unlike [`from_reader`], this does not check that the reader is exhausted,
+/// so the elements of a stream can be decoded one after the other.
+pub fn from_reader_partial<R: Read, T>(reader: R) -> Result<(T, R)>
+where
+    T: DeserializeOwned,
+{
+    let mut deserializer = Deserializer::new(reader);
+    let t = T::deserialize(&mut deserializer)?;
+    Ok((t, deserializer.reader.into_inner()))
+}
+
+impl<R: Read + Seek> Deserializer<R> {
+    /// Create a deserializer that reads SQLite JSONB data from a seekable
+    /// reader, such as a file. The payloads of skipped elements, such as
+    /// ignored fields, are seeked over instead of being read.
+    ///
+    /// The length of the input is measured first, by seeking to its end,
+    /// so that payloads that extend past it are reported with
+    /// [`Error::PayloadExceedsInput`] rather than being seeked over.
+    pub fn from_seekable_reader(mut reader: R) -> Result<Self> {
+        let start = reader.stream_position()?;
+        let end = reader.seek(SeekFrom::End(0))?;
+        reader.seek(SeekFrom::Start(start))?;
+        let len = usize::try_from(end.saturating_sub(start))
+            .map_err(usize_conversion)?;
+        let mut deserializer = Deserializer::new(reader);
+        deserializer.reader.set_input_len(len);
+        deserializer.reader.set_seek_forward(|reader, n| {
+            let n = i64::try_from(n).map_err(|_| {
+                std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "seek offset out of range",
+                )
+            })?;
+            reader.seek(SeekFrom::Current(n)).map(drop)
+        });
+        Ok(deserializer)
     }
 }
 
 impl<R: Read> Deserializer<R> {
-    fn with_header(&mut self, header: Header) -> Deserializer<impl Read + '_> {
-        // a little bit of a hack to "unread" a header that was already read
-        let header_bytes = std::io::Cursor::new(header.serialize());
-        let reader = header_bytes.chain(&mut self.reader);
-        Deserializer { reader }
+    /// Create a deserializer that reads SQLite JSONB data from a reader.
+    /// Values that are deserialized from it must own their data.
+    pub fn from_reader(reader: R) -> Self {
+        Deserializer::new(reader)
+    }
+
+    fn new(reader: R) -> Self {
+        Deserializer {
+            reader: LimitedCountingReader::new(reader),
+            position: 0,
+            container_end: None,
+            peeked: None,
+            elements: 0,
+            max_elements: None,
+            string_bytes: 0,
+            max_string_bytes: None,
+            depth: 0,
+            max_depth: None,
+            number_hook: None,
+            observer: None,
+            cancel: None,
+            #[cfg(feature = "bytes")]
+            shared_input: None,
+            human_readable: true,
+            lenient_numbers: false,
+            coerce_int_float: false,
+            lenient_text: false,
+            lossy_utf8: false,
+            #[cfg(feature = "base64")]
+            base64_bytes: false,
+            strict_empty_payloads: false,
+            allow_trailing_zeros: false,
+            allow_non_finite: false,
+            lenient_bool: false,
+            on_reserved: OnReserved::Error,
+            key_renames: None,
+            header_bytes: [0; 9],
+            header_len: 0,
+            scratch: Vec::new(),
+        }
+    }
+
+    /// Set all the options of `config`.
+    fn apply_config(mut self, config: &DeserializerConfig) -> Self {
+        let config = config.clone();
+        if let Some(max) = config.max_input_bytes {
+            self.reader.set_limit(max);
+        }
+        self.max_elements = config.max_elements;
+        self.max_string_bytes = config.max_string_bytes;
+        self.max_depth = config.max_depth;
+        self.number_hook = config.number_hook;
+        self.human_readable = config.human_readable;
+        self.lenient_numbers = config.lenient_numbers;
+        self.coerce_int_float = config.coerce_int_float;
+        self.lenient_text = config.lenient_text;
+        self.lossy_utf8 = config.lossy_utf8;
+        #[cfg(feature = "base64")]
+        {
+            self.base64_bytes = config.base64_bytes;
+        }
+        self.strict_empty_payloads = config.strict_empty_payloads;
+        self.allow_trailing_zeros = config.allow_trailing_zeros;
+        self.allow_non_finite = config.allow_non_finite;
+        self.lenient_bool = config.lenient_bool;
+        self.on_reserved = config.on_reserved;
+        self.key_renames = config.key_renames;
+        self
+    }
+
+    /// Limit the total number of elements (scalars, arrays and objects,
+    /// at any nesting depth) that may be decoded.
+    ///
+    /// Decoding fails with [`Error::TooManyElements`] when the input
+    /// contains more than `max` elements.
+    pub fn with_max_elements(mut self, max: usize) -> Self {
+        self.max_elements = Some(max);
+        self
+    }
+
+    /// Limit the number of bytes that may be read from the input.
+    ///
+    /// Decoding fails with [`Error::InputTooLarge`] as soon as more than
+    /// `max` bytes are read, including when checking for trailing data in
+    /// [`Self::end`].
+    pub fn with_max_input_bytes(mut self, max: usize) -> Self {
+        self.reader.set_limit(max);
+        self
+    }
+
+    /// Limit the total size, in bytes, of the payloads of the text elements
+    /// that are decoded, to bound the memory taken by the decoded strings.
+    /// Object keys count towards the limit.
+    ///
+    /// Decoding fails with [`Error::StringBudgetExceeded`] when the limit
+    /// is exceeded.
+    pub fn with_max_total_string_bytes(mut self, max: usize) -> Self {
+        self.max_string_bytes = Some(max);
+        self
+    }
+
+    /// Limit the number of arrays and objects that may be nested inside
+    /// each other. There is no limit by default.
+    ///
+    /// Decoding is recursive, so this protects against deeply nested input
+    /// overflowing the stack. A limit of 128, like `serde_json`'s recursion
+    /// limit, is a reasonable choice for untrusted input. Decoding fails
+    /// with [`Error::DepthLimitExceeded`] when the limit is reached.
+    pub fn with_max_depth(mut self, max: usize) -> Self {
+        self.max_depth = Some(max);
+        self
+    }
+
+    /// Parse the numbers that are decoded through `deserialize_any` with a
+    /// custom function, instead of the built-in parser.
+    ///
+    /// The function receives the raw text of the number, and the type of the
+    /// element it was stored in. This makes it possible to decode numbers
+    /// into arbitrary precision types.
+    pub fn with_number_visitor(
+        mut self,
+        hook: impl Fn(&str, ElementType) -> Result<ParsedNumber>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        self.number_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Call `observer` on every element header that the deserializer reads,
+    /// in the order they appear in the input, along with the byte offset of
+    /// the start of the header.
+    ///
+    /// Values that are skipped, such as unknown fields, are reported by
+    /// their own header only: the elements nested in them are skipped
+    /// without reading their headers.
+    pub fn with_observer(
+        mut self,
+        observer: impl FnMut(&Header, usize) + Send + 'static,
+    ) -> Self {
+        self.observer = Some(Box::new(observer));
+        self
+    }
+
+    /// Abort decoding with [`Error::Cancelled`] as soon as `cancel` is set,
+    /// for instance from another thread when a request times out.
+    ///
+    /// The flag is checked before every element header is read, so setting
+    /// it stops the decoding of even the largest arrays right away.
+    pub fn with_cancel_flag(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    /// Set the value returned by `is_human_readable`, which changes how some
+    /// types (such as IP addresses or UUIDs) expect to be deserialized.
+    ///
+    /// Defaults to `true`, for compatibility with types serialized to JSON.
+    pub fn with_human_readable(mut self, human_readable: bool) -> Self {
+        self.human_readable = human_readable;
+        self
+    }
+
+    /// Accept `Int` elements whose payload is surrounded by whitespace.
+    ///
+    /// By default, the payload of an `Int` must be an integer in canonical
+    /// form, as required by the JSONB specification, and anything else is
+    /// rejected with [`Error::NonCanonicalInteger`]. In lenient mode,
+    /// whitespace is trimmed before the payload is checked.
+    pub fn with_lenient_numbers(mut self, lenient: bool) -> Self {
+        self.lenient_numbers = lenient;
+        self
+    }
+
+    /// Accept `Int` elements whose payload is a float, such as `1.5`,
+    /// truncating it towards zero.
+    ///
+    /// Some producers mistakenly tag floats as integers. By default, such an
+    /// element is rejected with [`Error::IntegerHasFraction`] when it has a
+    /// fractional part.
+    pub fn with_coerce_int_float(mut self, coerce: bool) -> Self {
+        self.coerce_int_float = coerce;
+        self
+    }
+
+    /// Decode the payload of every text element, whatever its type, through
+    /// the JSON5 unescaper.
+    ///
+    /// By default, `Text` and `TextRaw` payloads are taken as they are, so a
+    /// producer that wrote escapes into them gets its backslashes back. In
+    /// lenient mode, such escapes are decoded as if the element was `Text5`.
+    pub fn with_lenient_text(mut self, lenient: bool) -> Self {
+        self.lenient_text = lenient;
+        self
+    }
+
+    /// Replace invalid UTF-8 sequences in `TextRaw` elements with
+    /// `U+FFFD REPLACEMENT CHARACTER`, instead of failing.
+    ///
+    /// This helps reading legacy data that was partially corrupted. By
+    /// default, decoding a `TextRaw` element that is not valid UTF-8 fails.
+    pub fn with_lossy_utf8(mut self, lossy: bool) -> Self {
+        self.lossy_utf8 = lossy;
+        self
+    }
+
+    /// Decode the text elements that are deserialized as byte strings from
+    /// base64, as written by a [`Serializer`](crate::Serializer) with
+    /// [`BytesEncoding::Base64`](crate::BytesEncoding::Base64).
+    ///
+    /// By default, the payload of the text element is used as is.
+    #[cfg(feature = "base64")]
+    pub fn with_base64_bytes(mut self, base64: bool) -> Self {
+        self.base64_bytes = base64;
+        self
+    }
+
+    /// Reject `Null`, `True` and `False` elements that declare a payload,
+    /// with [`Error::UnexpectedPayload`].
+    ///
+    /// These elements never have a payload when written by a valid encoder.
+    /// By default, the payload is skipped and ignored.
+    pub fn with_strict_empty_payloads(mut self, strict: bool) -> Self {
+        self.strict_empty_payloads = strict;
+        self
+    }
+
+    /// Accept floats that are NaN or infinite, such as the `Float5` payloads
+    /// `NaN` and `Infinity`, in `f32` and `f64` values.
+    ///
+    /// By default, they are rejected with [`Error::NonFiniteFloat`].
+    pub fn with_allow_non_finite(mut self, allow: bool) -> Self {
+        self.allow_non_finite = allow;
+        self
+    }
+
+    /// Decode booleans from `Int` elements holding `0` or `1`, and from
+    /// text elements holding exactly `true` or `false`, as stored by some
+    /// producers, besides `True` and `False` elements.
+    ///
+    /// Other integers and text are rejected. By default, only `True` and
+    /// `False` elements are booleans.
+    pub fn with_lenient_bool(mut self, lenient: bool) -> Self {
+        self.lenient_bool = lenient;
+        self
+    }
+
+    /// Choose how `deserialize_any` treats elements of the reserved types,
+    /// to keep decoding data written by a future version of the format
+    /// into self-describing types.
+    ///
+    /// By default, they are rejected with [`Error::ReservedElementType`],
+    /// which is what other deserialization methods always do. The value
+    /// returned by [`OnReserved::Custom`] is decoded with the default
+    /// options.
+    pub fn with_on_reserved(mut self, on_reserved: OnReserved) -> Self {
+        self.on_reserved = on_reserved;
+        self
+    }
+
+    /// Rename object keys before they are matched against field names:
+    /// a key found in `renames` is visited as the name it maps to.
+    ///
+    /// This adapts data to types whose field names cannot be changed, such
+    /// as types from other crates. Keys that are not in `renames` are
+    /// visited unchanged.
+    pub fn with_key_rename(mut self, renames: HashMap<String, String>) -> Self {
+        self.key_renames = Some(Arc::new(renames));
+        self
+    }
+
+    /// Accept zero bytes after the value in [`Self::end`], as left by
+    /// producers that pad blobs to a fixed width.
+    ///
+    /// Any other trailing byte is still rejected with
+    /// [`Error::TrailingCharacters`], which then counts the bytes from the
+    /// first one that is not zero.
+    pub fn with_allow_trailing_zeros(mut self, allow: bool) -> Self {
+        self.allow_trailing_zeros = allow;
+        self
+    }
+
+    /// Whether text decoded as bytes is base64 that needs decoding.
+    fn decodes_base64_bytes(&self) -> bool {
+        #[cfg(feature = "base64")]
+        {
+            self.base64_bytes
+        }
+        #[cfg(not(feature = "base64"))]
+        {
+            false
+        }
+    }
+
+    /// The position of the deserializer in the input, in bytes.
+    pub fn byte_offset(&self) -> usize {
+        self.position
+    }
+
+    /// Check that the input has been entirely consumed.
+    ///
+    /// This should be called after deserializing a value, to make sure
+    /// there is no trailing data after it. It fails as soon as trailing
+    /// data is found, without reading the rest of the input.
+    pub fn end(&mut self) -> Result<()> {
+        let padding = Header {
+            element_type: ElementType::Null,
+            payload_size: 0,
+        };
+        // the bytes of the trailing data that were already read
+        let (next_element_type, read) = match self.peeked.take() {
+            Some(header)
+                if !(self.allow_trailing_zeros && header == padding) =>
+            {
+                (header.element_type, self.header_len)
+            }
+            _ => {
+                let mut first_byte = [0u8];
+                loop {
+                    if self.reader.read(&mut first_byte)? == 0 {
+                        return Ok(());
+                    }
+                    if !(self.allow_trailing_zeros && first_byte[0] == 0) {
+                        break;
+                    }
+                }
+                (ElementType::from_header_byte(first_byte[0]), 1)
+            }
+        };
+        Err(Error::TrailingCharacters {
+            remaining: read + self.reader.input_left().unwrap_or(0),
+            next_element_type,
+        })
     }
 
     fn read_header(&mut self) -> Result<Header> {
+        self.read_header_checked(false)
+    }
+
+    /// Like [`Self::read_header`], but also returning headers of reserved
+    /// types when `allow_reserved` is set.
+    fn read_header_checked(&mut self, allow_reserved: bool) -> Result<Header> {
+        if let Some(cancel) = &self.cancel {
+            if cancel.load(Ordering::Relaxed) {
+                return Err(Error::Cancelled);
+            }
+        }
+        if let Some(header) = self.peeked.take() {
+            return Ok(header);
+        }
+        if self.container_end == Some(self.position) {
+            return Err(Error::Empty);
+        }
+        let offset = self.position;
+        let header = self.read_header_bytes()?;
+        if header.element_type.is_reserved() && !allow_reserved {
+            return Err(Error::ReservedElementType(header.element_type));
+        }
+        if let Some(end) = self.container_end {
+            let element_end = self.position.checked_add(header.payload_size);
+            if element_end.map_or(true, |element_end| element_end > end) {
+                return Err(Error::ElementExceedsContainer);
+            }
+        }
+
+        self.elements += 1;
+        if let Some(max) = self.max_elements {
+            if self.elements > max {
+                return Err(Error::TooManyElements(max));
+            }
+        }
+        if let Some(observer) = &mut self.observer {
+            observer(&header, offset);
+        }
+        Ok(header)
+    }
+
+    fn read_header_bytes(&mut self) -> Result<Header> {
         /*  The upper four bits of the first byte of the header determine
           - size of the header
           - and possibly also the size of the payload.
@@ -72,83 +748,266 @@ impl<R: Read> Deserializer<R> {
             return Err(Error::Empty);
         }
         let first_byte = header_0[0];
-        let upper_four_bits = first_byte >> 4;
-        /*
-         If the upper four bits have a value between 0 and 11,
-        then the header is exactly one byte in size and the payload size is determined by those upper four bits.
-
-        If the upper four bits have a value between 12 and 15,
-        that means that the total header size is 2, 3, 5, or 9 bytes and the payload size is unsigned big-endian integer that is contained in the subsequent bytes.
-
-        The size integer is
-          - the one byte that following the initial header byte if the upper four bits are 12,
-          - two bytes if the upper bits are 13,
-          - four bytes if the upper bits are 14,
-          - and eight bytes if the upper bits are 15.
-        */
-        let bytes_to_read = match upper_four_bits {
-            0..=11 => 0,
-            12 => 1,
-            13 => 2,
-            14 => 4,
-            15 => 8,
-            n => unreachable!("{n} does not fit in four bits"),
-        };
-        let payload_size: usize = if bytes_to_read == 0 {
-            usize::from(upper_four_bits)
-        } else {
-            let mut buf = [0u8; 8];
-            let start = 8 - bytes_to_read;
-            self.reader.read_exact(&mut buf[start..8])?;
-            usize::from_be_bytes(buf)
-        };
-        Ok(Header {
-            element_type: ElementType::from(first_byte),
-            payload_size,
-        })
+        let size_len = Header::size_field_len(first_byte);
+        self.header_bytes[0] = first_byte;
+        let size_field = &mut self.header_bytes[1..1 + size_len];
+        let got = read_fully(&mut self.reader, size_field)?;
+        if got < size_len {
+            return Err(Error::TruncatedHeader {
+                expected: size_len,
+                got,
+            });
+        }
+        self.header_len = 1 + size_len;
+        self.position += 1 + size_len;
+        Ok(Header::from_parts(
+            first_byte,
+            &self.header_bytes[1..1 + size_len],
+        ))
     }
 
     fn read_payload_string(&mut self, header: Header) -> Result<String> {
-        let mut str = String::with_capacity(header.payload_size);
+        // do not trust the header with a large up-front allocation: the
+        // string grows with the data actually read
+        let mut str = String::with_capacity(
+            header.payload_size.min(SCRATCH_PREALLOCATION_LIMIT),
+        );
         let read = self.reader_with_limit(header)?.read_to_string(&mut str)?;
-        assert_eq!(read, header.payload_size);
+        if read != header.payload_size {
+            return Err(Error::UnexpectedEof);
+        }
         Ok(str)
     }
 
+    /// Read the whole payload into the scratch buffer, and call `f` on it.
+    fn with_scratch_payload<T>(
+        &mut self,
+        header: Header,
+        f: impl FnOnce(&[u8]) -> Result<T>,
+    ) -> Result<T> {
+        let mut buf = std::mem::take(&mut self.scratch);
+        buf.clear();
+        {
+            let mut reader = self.reader_with_limit(header)?;
+            if header.payload_size <= SCRATCH_PREALLOCATION_LIMIT {
+                buf.resize(header.payload_size, 0);
+                reader.read_exact(&mut buf)?;
+            } else {
+                // do not trust the header with a large up-front allocation
+                reader.read_to_end(&mut buf)?;
+                if buf.len() != header.payload_size {
+                    return Err(Error::UnexpectedEof);
+                }
+            }
+        }
+        let result = f(&buf);
+        self.scratch = buf;
+        result
+    }
+
     fn drop_payload(&mut self, header: Header) -> Result<ElementType> {
-        let mut remaining = header.payload_size;
-        while remaining > 0 {
-            let mut buf = [0u8; 256];
-            let len = buf.len().min(remaining);
-            self.reader.read_exact(&mut buf[..len])?;
-            remaining -= len;
+        self.consume_payload(header)?;
+        let dropped = self.reader.skip(header.payload_size)?;
+        if dropped != header.payload_size {
+            return Err(Error::UnexpectedEof);
         }
         Ok(header.element_type)
     }
 
-    fn read_bool(&mut self, header: Header) -> Result<bool> {
+    /// Skip the elements left in the current container,
+    /// returning how many there were.
+    fn skip_remaining_elements(&mut self) -> Result<usize> {
+        let mut skipped = 0;
+        loop {
+            match self.read_header() {
+                Ok(header) => self.drop_payload(header)?,
+                Err(Error::Empty) => return Ok(skipped),
+                Err(e) => return Err(e),
+            };
+            skipped += 1;
+        }
+    }
+
+    /// Skip the payload of an element whose type never has one.
+    fn drop_empty_payload(&mut self, header: Header) -> Result<()> {
+        if self.strict_empty_payloads && header.payload_size != 0 {
+            return Err(Error::UnexpectedPayload(header.element_type));
+        }
         self.drop_payload(header)?;
+        Ok(())
+    }
+
+    /// Read the next element without decoding it, and return its bytes,
+    /// header included.
+    fn read_raw_element(&mut self) -> Result<Vec<u8>> {
+        // a peeked header is always the last one that was read
+        let header = self.read_header()?;
+        let header_bytes = self.header_bytes;
+        let header_len = self.header_len;
+        self.with_scratch_payload(header, |payload| {
+            let mut bytes = Vec::with_capacity(header_len + payload.len());
+            bytes.extend_from_slice(&header_bytes[..header_len]);
+            bytes.extend_from_slice(payload);
+            Ok(bytes)
+        })
+    }
+
+    /// Skip an element, walking through arrays and objects to check that
+    /// the headers of their contents are valid.
+    fn skip_element(&mut self, header: Header) -> Result<()> {
         match header.element_type {
-            ElementType::True => Ok(true),
-            ElementType::False => Ok(false),
-            t => Err(Error::UnexpectedType(t)),
+            ElementType::Array | ElementType::Object => {
+                let children = self.in_container(header, |de| {
+                    let mut children = 0usize;
+                    loop {
+                        match de.read_header() {
+                            Ok(header) => de.skip_element(header)?,
+                            Err(Error::Empty) => return Ok(children),
+                            Err(e) => return Err(e),
+                        }
+                        children += 1;
+                    }
+                })?;
+                if header.element_type == ElementType::Object
+                    && children % 2 != 0
+                {
+                    // a key without a value
+                    return Err(Error::MissingObjectValue);
+                }
+            }
+            _ => {
+                self.drop_payload(header)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn read_bool(&mut self, header: Header) -> Result<bool> {
+        let lenient = match header.element_type {
+            t if self.lenient_bool && t.is_text() => self.read_text(header)?,
+            ElementType::Int if self.lenient_bool => {
+                self.read_payload_string(header)?
+            }
+            _ => {
+                let value = match header.element_type {
+                    ElementType::True => true,
+                    ElementType::False => false,
+                    t => {
+                        return Err(Error::UnexpectedType {
+                            expected: "True or False",
+                            found: t,
+                        })
+                    }
+                };
+                self.drop_empty_payload(header)?;
+                return Ok(value);
+            }
+        };
+        match (header.element_type, lenient.as_str()) {
+            (ElementType::Int, "1") => Ok(true),
+            (ElementType::Int, "0") => Ok(false),
+            (t, "true") if t.is_text() => Ok(true),
+            (t, "false") if t.is_text() => Ok(false),
+            (_, other) => {
+                Err(Error::Message(format!("{other:?} is not a boolean")))
+            }
         }
     }
 
     fn read_null(&mut self, header: Header) -> Result<()> {
-        self.drop_payload(header)?;
         match header.element_type {
-            ElementType::Null => Ok(()),
-            t => Err(Error::UnexpectedType(t)),
+            ElementType::Null => self.drop_empty_payload(header),
+            t => Err(Error::UnexpectedType {
+                expected: "Null",
+                found: t,
+            }),
+        }
+    }
+
+    /// Check that the payload of the given header fits in the input, when
+    /// the length of the input is known, and within
+    /// [`Self::with_max_input_bytes`], to report truncated or oversized
+    /// input before trying to read it.
+    fn check_available(&self, header: Header) -> Result<()> {
+        match self.reader.available() {
+            Some(available) if header.payload_size > available => {
+                match (self.reader.input_left(), self.reader.limit()) {
+                    (Some(left), _) if header.payload_size > left => {
+                        Err(Error::PayloadExceedsInput {
+                            declared: header.payload_size,
+                            available: left,
+                        })
+                    }
+                    (_, Some(max)) => Err(Error::InputTooLarge(max)),
+                    // only the limit or the input can make it unavailable
+                    (_, None) => unreachable!(),
+                }
+            }
+            _ => Ok(()),
         }
     }
 
+    /// Returns a reader over the payload of the given header.
+    /// The payload is considered consumed once this is called.
     fn reader_with_limit(&mut self, header: Header) -> Result<impl Read + '_> {
         let limit =
             u64::try_from(header.payload_size).map_err(usize_conversion)?;
+        self.consume_payload(header)?;
         Ok((&mut self.reader).take(limit))
     }
 
+    /// Move the position past the payload of the given header, after
+    /// checking that the input can hold it.
+    fn consume_payload(&mut self, header: Header) -> Result<()> {
+        self.check_available(header)?;
+        // no input can be long enough to hold a payload that overflows
+        self.position = self
+            .position
+            .checked_add(header.payload_size)
+            .ok_or(Error::UnexpectedEof)?;
+        Ok(())
+    }
+
+    /// Decode the elements of the array or object with the given header,
+    /// making sure they span exactly its payload.
+    fn in_container<T>(
+        &mut self,
+        header: Header,
+        f: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        let end = self
+            .position
+            .checked_add(header.payload_size)
+            .ok_or(Error::UnexpectedEof)?;
+        if let Some(max) = self.max_depth {
+            if self.depth >= max {
+                return Err(Error::DepthLimitExceeded(max));
+            }
+        }
+        self.check_available(header)?;
+        let parent_end = self.container_end.replace(end);
+        self.depth += 1;
+        let value = f(self);
+        self.depth -= 1;
+        let value = value?;
+        if let Some(header) = self.peeked {
+            return Err(Error::TrailingCharacters {
+                remaining: end - self.position,
+                next_element_type: header.element_type,
+            });
+        }
+        if self.position != end {
+            let mut first_byte = [0u8];
+            self.reader.read_exact(&mut first_byte)?;
+            return Err(Error::TrailingCharacters {
+                remaining: end - self.position,
+                next_element_type: ElementType::from_header_byte(first_byte[0]),
+            });
+        }
+        self.container_end = parent_end;
+        Ok(value)
+    }
+
     fn read_json_compatible<T>(&mut self, header: Header) -> Result<T>
     where
         for<'a> T: Deserialize<'a>,
@@ -157,11 +1016,12 @@ impl<R: Read> Deserializer<R> {
             // micro-optimization: read small payloads into a stack buffer
             let mut buf = [0u8; 8];
             let smallbuf = &mut buf[..header.payload_size];
-            self.reader.read_exact(smallbuf)?;
+            self.reader_with_limit(header)?.read_exact(smallbuf)?;
             Ok(crate::json::parse_json_slice(smallbuf)?)
         } else {
-            let mut reader = self.reader_with_limit(header)?;
-            Ok(crate::json::parse_json(&mut reader)?)
+            self.with_scratch_payload(header, |payload| {
+                Ok(crate::json::parse_json_slice(payload)?)
+            })
         }
     }
 
@@ -189,25 +1049,179 @@ impl<R: Read> Deserializer<R> {
         Ok(crate::json::parse_json5(&mut reader)?)
     }
 
+    /// Decode an integer element into `T`, failing with
+    /// [`Error::NumberOutOfRange`] if it does not fit.
     fn read_integer<T>(&mut self, header: Header) -> Result<T>
     where
-        for<'a> T: Deserialize<'a>,
+        T: TryFrom<i128> + TryFrom<u128>,
     {
+        self.read_wide_integer(header)?.convert()
+    }
+
+    fn read_wide_integer(&mut self, header: Header) -> Result<WideInteger> {
         match header.element_type {
-            ElementType::Int => self.read_json_compatible(header),
-            ElementType::Int5 => self.read_json5_compatible(header),
-            t => Err(Error::UnexpectedType(t)),
+            ElementType::Int => {
+                let lenient = self.lenient_numbers;
+                let coerce = self.coerce_int_float;
+                self.with_scratch_payload(header, |mut payload| {
+                    if lenient {
+                        payload = trim_ascii_whitespace(payload);
+                    }
+                    if is_canonical_integer(payload) {
+                        return parse_canonical_integer(payload);
+                    }
+                    let text = || String::from_utf8_lossy(payload).into();
+                    match split_fraction(payload) {
+                        // truncated as text, which loses no precision
+                        Some((integer, _)) if coerce => {
+                            parse_canonical_integer(integer)
+                        }
+                        Some((_, fraction))
+                            if fraction.iter().any(|&c| c != b'0') =>
+                        {
+                            Err(Error::IntegerHasFraction(text()))
+                        }
+                        _ => Err(Error::NonCanonicalInteger(text())),
+                    }
+                })
+            }
+            ElementType::Int5 => self.with_scratch_payload(header, |payload| {
+                // serde_json5 fails on negative hexadecimal integers
+                match parse_hex_integer(payload) {
+                    Some(n) if cfg!(feature = "serde_json5") => return n,
+                    _ => {}
+                }
+                let n: AnyInteger = crate::json::parse_json5(&mut &*payload)?;
+                Ok(match n {
+                    AnyInteger::Signed(i) => WideInteger::Signed(i.into()),
+                    AnyInteger::Unsigned(u) => WideInteger::Unsigned(u.into()),
+                })
+            }),
+            t => Err(Error::UnexpectedType {
+                expected: "Int or Int5",
+                found: t,
+            }),
         }
     }
 
-    fn read_string(&mut self, header: Header) -> Result<String> {
-        match header.element_type {
-            ElementType::Text | ElementType::TextRaw => {
+    /// Add the payload of a text element to the total checked by
+    /// [`Self::with_max_total_string_bytes`].
+    fn count_string_bytes(&mut self, header: Header) -> Result<()> {
+        self.string_bytes =
+            self.string_bytes.saturating_add(header.payload_size);
+        match self.max_string_bytes {
+            Some(max) if self.string_bytes > max => {
+                Err(Error::StringBudgetExceeded(max))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Decode any of the text element types into a string.
+    /// All the places where a string is expected go through this function,
+    /// or through [`Self::visit_text`], which decodes text the same way.
+    /// The whole payload is the content of the string: bytes that a JSON
+    /// parser would ignore, such as trailing whitespace, are part of it.
+    fn read_text(&mut self, header: Header) -> Result<String> {
+        let decoding = self.text_decoding(header)?;
+        self.decode_text(header, decoding)
+    }
+
+    /// Check that `header` is the header of a text element, count its
+    /// payload against the string budget, and choose how to decode it
+    /// according to the options of the deserializer.
+    fn text_decoding(&mut self, header: Header) -> Result<TextDecoding> {
+        let element_type = header.element_type;
+        if !element_type.is_text() {
+            return Err(Error::UnexpectedType {
+                expected: "a text element",
+                found: element_type,
+            });
+        }
+        self.count_string_bytes(header)?;
+        Ok(match element_type {
+            _ if self.lenient_text => TextDecoding::Json5,
+            ElementType::TextJ => TextDecoding::Json,
+            ElementType::Text5 => TextDecoding::Json5,
+            ElementType::TextRaw => TextDecoding::Verbatim {
+                lossy: self.lossy_utf8,
+            },
+            _ => TextDecoding::Verbatim { lossy: false },
+        })
+    }
+
+    /// Read the payload of a text element into a string.
+    fn decode_text(
+        &mut self,
+        header: Header,
+        decoding: TextDecoding,
+    ) -> Result<String> {
+        match decoding {
+            TextDecoding::Verbatim { lossy: false } => {
                 self.read_payload_string(header)
             }
-            ElementType::TextJ => self.read_json_compatible_string(header),
-            ElementType::Text5 => self.read_json5_compatible_string(header),
-            t => Err(Error::UnexpectedType(t)),
+            TextDecoding::Verbatim { lossy: true } => self
+                .with_scratch_payload(header, |payload| {
+                    Ok(String::from_utf8_lossy(payload).into_owned())
+                }),
+            TextDecoding::Json => self.read_json_compatible_string(header),
+            TextDecoding::Json5 => self.read_json5_compatible_string(header),
+        }
+    }
+
+    /// Hand the payload of a text element that needs no decoding to the
+    /// visitor of [`shared_text`](crate::shared_text) as a slice of the
+    /// shared input, and any other text as a string.
+    #[cfg(feature = "bytes")]
+    fn visit_shared_text<'de, V>(&mut self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let header = self.read_header()?;
+        let decoding = self.text_decoding(header)?;
+        if let (Some(input), TextDecoding::Verbatim { .. }) =
+            (&self.shared_input, decoding)
+        {
+            self.check_available(header)?;
+            let end = self.position + header.payload_size;
+            let text = input.slice(self.position..end);
+            if std::str::from_utf8(&text).is_ok() {
+                self.drop_payload(header)?;
+                crate::shared_text::hand_over(text);
+                return visitor.visit_unit();
+            }
+        }
+        visitor.visit_string(self.decode_text(header, decoding)?)
+    }
+
+    /// Like [`Self::read_text`], but hands the visitor a string borrowed from
+    /// the scratch buffer, instead of an owned one, when the payload needs
+    /// no decoding.
+    fn visit_text<'de, V>(
+        &mut self,
+        header: Header,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.text_decoding(header)? {
+            TextDecoding::Verbatim { lossy } => {
+                self.with_scratch_payload(header, |payload| {
+                    match std::str::from_utf8(payload) {
+                        Ok(s) => visitor.visit_str(s),
+                        Err(_) if lossy => {
+                            visitor.visit_str(&String::from_utf8_lossy(payload))
+                        }
+                        Err(_) => Err(String::from_utf8(payload.to_vec())
+                            .expect_err("invalid utf8")
+                            .into()),
+                    }
+                })
+            }
+            decoding => {
+                visitor.visit_string(self.decode_text(header, decoding)?)
+            }
         }
     }
 
@@ -220,10 +1234,39 @@ impl<R: Read> Deserializer<R> {
             ElementType::Int5 => self.read_json5_compatible(header),
             ElementType::Float => self.read_json_compatible(header),
             ElementType::Float5 => self.read_json5_compatible(header),
-            t => Err(Error::UnexpectedType(t)),
+            t => Err(Error::UnexpectedType {
+                expected: "a number",
+                found: t,
+            }),
         }
     }
 
+    /// Hand the elements of an array to `visitor`. Arrays are decoded through
+    /// this function in `deserialize_any` as well as in the typed paths, so
+    /// that they behave the same.
+    fn visit_array<'de, V>(
+        &mut self,
+        header: Header,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.in_container(header, |de| visitor.visit_seq(de))
+    }
+
+    /// Hand the entries of an object to `visitor`, like [`Self::visit_array`].
+    fn visit_object<'de, V>(
+        &mut self,
+        header: Header,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.in_container(header, |de| visitor.visit_map(de))
+    }
+
     fn deserialize_any_with_header<'de, V>(
         &mut self,
         header: Header,
@@ -233,6 +1276,21 @@ impl<R: Read> Deserializer<R> {
         V: Visitor<'de>,
     {
         match header.element_type {
+            ElementType::Int
+            | ElementType::Int5
+            | ElementType::Float
+            | ElementType::Float5
+                if self.number_hook.is_some() =>
+            {
+                let raw = self.read_payload_string(header)?;
+                let hook = self.number_hook.as_ref().expect("checked above");
+                match hook(&raw, header.element_type)? {
+                    ParsedNumber::U64(n) => visitor.visit_u64(n),
+                    ParsedNumber::I64(n) => visitor.visit_i64(n),
+                    ParsedNumber::F64(n) => visitor.visit_f64(n),
+                    ParsedNumber::String(s) => visitor.visit_string(s),
+                }
+            }
             ElementType::Null => {
                 self.read_null(header)?;
                 visitor.visit_unit()
@@ -244,58 +1302,249 @@ impl<R: Read> Deserializer<R> {
                 visitor.visit_f64(self.read_float(header)?)
             }
             ElementType::Int | ElementType::Int5 => {
-                let i: i64 = self.read_integer(header)?;
-                if let Ok(x) = u8::try_from(i) {
-                    visitor.visit_u8(x)
-                } else if let Ok(x) = i8::try_from(i) {
-                    visitor.visit_i8(x)
-                } else if let Ok(x) = u16::try_from(i) {
-                    visitor.visit_u16(x)
-                } else if let Ok(x) = i16::try_from(i) {
-                    visitor.visit_i16(x)
-                } else if let Ok(x) = u32::try_from(i) {
-                    visitor.visit_u32(x)
-                } else if let Ok(x) = i32::try_from(i) {
-                    visitor.visit_i32(x)
-                } else if let Ok(x) = u64::try_from(i) {
-                    visitor.visit_u64(x)
-                } else {
-                    visitor.visit_i64(i)
+                // read_integer picks the json or json5 parser from the
+                // exact element type, so hex Int5 payloads are supported
+                match self.read_wide_integer(header)? {
+                    WideInteger::Signed(i) => match i64::try_from(i) {
+                        Ok(i) => visitor.visit_i64(i),
+                        Err(_) => visitor.visit_i128(i),
+                    },
+                    WideInteger::Unsigned(u) => {
+                        if let Ok(i) = i64::try_from(u) {
+                            visitor.visit_i64(i)
+                        } else if let Ok(u) = u64::try_from(u) {
+                            visitor.visit_u64(u)
+                        } else {
+                            visitor.visit_u128(u)
+                        }
+                    }
                 }
             }
-            ElementType::Array => visitor.visit_seq(self),
-            ElementType::Object => visitor.visit_map(self),
+            ElementType::Array => self.visit_array(header, visitor),
+            ElementType::Object => self.visit_object(header, visitor),
             ElementType::Text
             | ElementType::TextJ
             | ElementType::Text5
             | ElementType::TextRaw => {
-                visitor.visit_string(self.read_string(header)?)
+                visitor.visit_string(self.read_text(header)?)
             }
-            ElementType::Reserved13
+            t @ (ElementType::Reserved13
             | ElementType::Reserved14
-            | ElementType::Reserved15 => {
-                Err(Error::UnexpectedType(header.element_type))
+            | ElementType::Reserved15) => match self.on_reserved {
+                OnReserved::Error => Err(Error::ReservedElementType(t)),
+                OnReserved::SkipAsNull => {
+                    self.drop_payload(header)?;
+                    visitor.visit_unit()
+                }
+                OnReserved::Custom(replace) => {
+                    let replacement = self
+                        .with_scratch_payload(header, |payload| {
+                            replace(t, payload)
+                        })?;
+                    let mut de = Deserializer::from_bytes(&replacement);
+                    let value =
+                        de::Deserializer::deserialize_any(&mut de, visitor)?;
+                    de.end()?;
+                    Ok(value)
+                }
+            },
+        }
+    }
+}
+
+/// An integer that fits in either an `i64` or a `u64`.
+enum AnyInteger {
+    Signed(i64),
+    Unsigned(u64),
+}
+
+impl<'de> Deserialize<'de> for AnyInteger {
+    fn deserialize<D: de::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        struct AnyIntegerVisitor;
+        impl<'de> Visitor<'de> for AnyIntegerVisitor {
+            type Value = AnyInteger;
+            fn expecting(
+                &self,
+                f: &mut std::fmt::Formatter,
+            ) -> std::fmt::Result {
+                f.write_str("an integer")
+            }
+            fn visit_i64<E>(
+                self,
+                v: i64,
+            ) -> std::result::Result<AnyInteger, E> {
+                Ok(AnyInteger::Signed(v))
             }
+            fn visit_u64<E>(
+                self,
+                v: u64,
+            ) -> std::result::Result<AnyInteger, E> {
+                Ok(AnyInteger::Unsigned(v))
+            }
+        }
+        deserializer.deserialize_any(AnyIntegerVisitor)
+    }
+}
+
+/// An integer, in the widest type for its sign, before it is converted to
+/// the type that is being deserialized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WideInteger {
+    /// A negative integer
+    Signed(i128),
+    Unsigned(u128),
+}
+
+impl WideInteger {
+    fn convert<T>(self) -> Result<T>
+    where
+        T: TryFrom<i128> + TryFrom<u128>,
+    {
+        let converted = match self {
+            WideInteger::Signed(i) => T::try_from(i).ok(),
+            WideInteger::Unsigned(u) => T::try_from(u).ok(),
+        };
+        converted.ok_or_else(|| Error::NumberOutOfRange {
+            value: self.to_string(),
+            target: std::any::type_name::<T>(),
+        })
+    }
+}
+
+impl std::fmt::Display for WideInteger {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            WideInteger::Signed(i) => i.fmt(f),
+            WideInteger::Unsigned(u) => u.fmt(f),
+        }
+    }
+}
+
+/// Parse an integer for which [`is_canonical_integer`] holds.
+fn parse_canonical_integer(payload: &[u8]) -> Result<WideInteger> {
+    // the payload only contains ASCII characters
+    let text = String::from_utf8_lossy(payload);
+    let parsed = if text.starts_with('-') {
+        text.parse().map(WideInteger::Signed).ok()
+    } else {
+        text.parse().map(WideInteger::Unsigned).ok()
+    };
+    // only an integer with too many digits can fail to parse
+    parsed.ok_or_else(|| Error::NumberOutOfRange {
+        value: text.into_owned(),
+        target: "i128 or u128",
+    })
+}
+
+/// Parse a JSON5 hexadecimal integer, such as `-0x1F`, or return `None` if
+/// `payload` is not one.
+fn parse_hex_integer(payload: &[u8]) -> Option<Result<WideInteger>> {
+    let (negative, unsigned) = match payload {
+        [b'-', rest @ ..] => (true, rest),
+        [b'+', rest @ ..] => (false, rest),
+        _ => (false, payload),
+    };
+    let digits = unsigned
+        .strip_prefix(b"0x")
+        .or_else(|| unsigned.strip_prefix(b"0X"))?;
+    if digits.is_empty() || !digits.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    let out_of_range = || Error::NumberOutOfRange {
+        value: String::from_utf8_lossy(payload).into_owned(),
+        target: "i128 or u128",
+    };
+    let digits = std::str::from_utf8(digits).expect("hex digits are ASCII");
+    let magnitude = match u128::from_str_radix(digits, 16) {
+        Ok(magnitude) => magnitude,
+        Err(_) => return Some(Err(out_of_range())),
+    };
+    Some(if !negative {
+        Ok(WideInteger::Unsigned(magnitude))
+    } else if magnitude <= i128::MIN.unsigned_abs() {
+        // wraps only for i128::MIN, which is its own negation
+        Ok(WideInteger::Signed((magnitude as i128).wrapping_neg()))
+    } else {
+        Err(out_of_range())
+    })
+}
+
+/// Whether `payload` is an integer in canonical decimal form: an optional
+/// minus sign followed by digits, without leading zeros.
+fn is_canonical_integer(payload: &[u8]) -> bool {
+    let digits = payload.strip_prefix(b"-").unwrap_or(payload);
+    match digits {
+        [b'0'] => true,
+        [b'1'..=b'9', rest @ ..] => rest.iter().all(u8::is_ascii_digit),
+        _ => false,
+    }
+}
+
+/// Split a number made of an integer in canonical form and a fractional
+/// part, such as `-1.50`, into the digits before and after the point.
+fn split_fraction(payload: &[u8]) -> Option<(&[u8], &[u8])> {
+    let point = payload.iter().position(|&c| c == b'.')?;
+    let (integer, fraction) = (&payload[..point], &payload[point + 1..]);
+    let valid = is_canonical_integer(integer)
+        && !fraction.is_empty()
+        && fraction.iter().all(u8::is_ascii_digit);
+    valid.then_some((integer, fraction))
+}
+
+fn trim_ascii_whitespace(mut bytes: &[u8]) -> &[u8] {
+    while let [first, rest @ ..] = bytes {
+        if !first.is_ascii_whitespace() {
+            break;
+        }
+        bytes = rest;
+    }
+    while let [rest @ .., last] = bytes {
+        if !last.is_ascii_whitespace() {
+            break;
         }
+        bytes = rest;
     }
+    bytes
 }
 
 fn read_with_quotes(r: impl Read) -> impl Read {
     b"\"".chain(r).chain(&b"\""[..])
 }
 
+/// Read into `buf` until it is full or the reader is exhausted,
+/// returning the number of bytes read.
+fn read_fully(mut reader: impl Read, buf: &mut [u8]) -> Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(read)
+}
+
 fn usize_conversion(e: std::num::TryFromIntError) -> Error {
     Error::Io(std::io::Error::new(std::io::ErrorKind::InvalidData, e))
 }
 
-impl<'de, 'a, R: Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
+impl<'de, R: Read> de::Deserializer<'de> for &mut Deserializer<R> {
     type Error = Error;
 
+    fn is_human_readable(&self) -> bool {
+        self.human_readable
+    }
+
     fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        let header = self.read_header()?;
+        let allow_reserved = !matches!(self.on_reserved, OnReserved::Error);
+        let header = self.read_header_checked(allow_reserved)?;
         self.deserialize_any_with_header(header, visitor)
     }
 
@@ -339,6 +1588,14 @@ impl<'de, 'a, R: Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
         visitor.visit_i64(self.read_integer(header)?)
     }
 
+    fn deserialize_i128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let header = self.read_header()?;
+        visitor.visit_i128(self.read_integer(header)?)
+    }
+
     fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
@@ -371,16 +1628,25 @@ impl<'de, 'a, R: Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
         visitor.visit_u64(self.read_integer(header)?)
     }
 
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let header = self.read_header()?;
+        visitor.visit_u128(self.read_integer(header)?)
+    }
+
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
         let header = self.read_header()?;
         if header.element_type == ElementType::Null {
+            self.read_null(header)?;
             visitor.visit_none()
         } else {
-            let mut deser = self.with_header(header);
-            visitor.visit_some(&mut deser)
+            self.peeked = Some(header);
+            visitor.visit_some(self)
         }
     }
 
@@ -406,12 +1672,33 @@ impl<'de, 'a, R: Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
 
     fn deserialize_newtype_struct<V>(
         self,
-        _name: &'static str,
+        name: &'static str,
         visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
+        if name == crate::raw_value::TOKEN {
+            return visitor.visit_byte_buf(self.read_raw_element()?);
+        }
+        if name == crate::number_text::TOKEN {
+            let header = self.read_header()?;
+            return match header.element_type {
+                // the JSON5 text of Int5 and Float5 elements, such as `0x1F`
+                // or `.5`, is not understood by `FromStr` implementations
+                ElementType::Int | ElementType::Float => {
+                    visitor.visit_string(self.read_payload_string(header)?)
+                }
+                t => Err(Error::UnexpectedType {
+                    expected: "a number",
+                    found: t,
+                }),
+            };
+        }
+        #[cfg(feature = "bytes")]
+        if name == crate::shared_text::TOKEN {
+            return self.visit_shared_text(visitor);
+        }
         visitor.visit_newtype_struct(self)
     }
 
@@ -420,28 +1707,54 @@ impl<'de, 'a, R: Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
         V: Visitor<'de>,
     {
         let head = self.read_header()?;
-        let reader = self.reader_with_limit(head)?;
-        let mut seq_deser = Deserializer { reader };
-        visitor.visit_seq(&mut seq_deser)
+        match head.element_type {
+            ElementType::Array => self.visit_array(head, visitor),
+            // an object is a sequence of (key, value) pairs, in order
+            ElementType::Object => {
+                self.in_container(head, |de| visitor.visit_seq(ObjectPairs(de)))
+            }
+            t => Err(Error::UnexpectedType {
+                expected: "Array or Object",
+                found: t,
+            }),
+        }
     }
 
-    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        let header = self.read_header()?;
+        if header.element_type != ElementType::Array {
+            return Err(Error::UnexpectedType {
+                expected: "Array",
+                found: header.element_type,
+            });
+        }
+        self.in_container(header, |de| {
+            let value = visitor.visit_seq(&mut *de)?;
+            let extra = de.skip_remaining_elements()?;
+            if extra > 0 {
+                let expected = format!("an array of length {len}");
+                return Err(de::Error::invalid_length(
+                    len + extra,
+                    &expected.as_str(),
+                ));
+            }
+            Ok(value)
+        })
     }
 
     fn deserialize_tuple_struct<V>(
         self,
         _name: &'static str,
-        _len: usize,
+        len: usize,
         visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        self.deserialize_tuple(len, visitor)
     }
 
     fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
@@ -449,9 +1762,13 @@ impl<'de, 'a, R: Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
         V: Visitor<'de>,
     {
         let head = self.read_header()?;
-        let reader = self.reader_with_limit(head)?;
-        let mut seq_deser = Deserializer { reader };
-        visitor.visit_map(&mut seq_deser)
+        match head.element_type {
+            ElementType::Object => self.visit_object(head, visitor),
+            t => Err(Error::UnexpectedType {
+                expected: "Object",
+                found: t,
+            }),
+        }
     }
 
     fn deserialize_struct<V>(
@@ -481,20 +1798,16 @@ impl<'de, 'a, R: Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
             | ElementType::TextJ
             | ElementType::Text5
             | ElementType::TextRaw => {
-                let s = self.read_string(header)?;
+                let s = self.read_text(header)?;
                 visitor.visit_enum(s.into_deserializer())
             }
             ElementType::Object => {
-                let reader = self.reader_with_limit(header)?;
-                let mut de = Deserializer { reader };
-                let r = visitor.visit_enum(&mut de);
-                if de.reader.read(&mut [0])? == 0 {
-                    r
-                } else {
-                    Err(Error::TrailingCharacters)
-                }
+                self.in_container(header, |de| visitor.visit_enum(de))
             }
-            other => Err(Error::UnexpectedType(other)),
+            other => Err(Error::UnexpectedType {
+                expected: "a text element or Object",
+                found: other,
+            }),
         }
     }
 
@@ -502,7 +1815,8 @@ impl<'de, 'a, R: Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
     where
         V: Visitor<'de>,
     {
-        self.deserialize_str(visitor)
+        let header = self.read_header()?;
+        self.visit_text(header, visitor)
     }
 
     fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
@@ -510,7 +1824,15 @@ impl<'de, 'a, R: Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
         V: Visitor<'de>,
     {
         let header = self.read_header()?;
-        self.drop_payload(header)?;
+        if self.depth == 0 {
+            // at the root, the whole value is validated, which makes
+            // `from_slice::<IgnoredAny>` a cheap well-formedness check
+            self.skip_element(header)?;
+        } else {
+            // elsewhere, such as in unknown struct fields, the size in the
+            // header is trusted
+            self.drop_payload(header)?;
+        }
         visitor.visit_unit()
     }
 
@@ -519,7 +1841,11 @@ impl<'de, 'a, R: Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
         V: Visitor<'de>,
     {
         let header = self.read_header()?;
-        visitor.visit_f32(self.read_float(header)?)
+        let v: f32 = self.read_float(header)?;
+        if !v.is_finite() && !self.allow_non_finite {
+            return Err(Error::NonFiniteFloat);
+        }
+        visitor.visit_f32(v)
     }
 
     fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value>
@@ -527,7 +1853,11 @@ impl<'de, 'a, R: Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
         V: Visitor<'de>,
     {
         let header = self.read_header()?;
-        visitor.visit_f64(self.read_float(header)?)
+        let v: f64 = self.read_float(header)?;
+        if !v.is_finite() && !self.allow_non_finite {
+            return Err(Error::NonFiniteFloat);
+        }
+        visitor.visit_f64(v)
     }
 
     fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
@@ -535,21 +1865,24 @@ impl<'de, 'a, R: Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
         V: Visitor<'de>,
     {
         let header = self.read_header()?;
-        let s = self.read_string(header)?;
-        if s.len() != 1 {
-            return Err(Error::Message(
-                "invalid string length for char".into(),
-            ));
+        let s = self.read_text(header)?;
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => visitor.visit_char(c),
+            _ => Err(Error::Message("invalid string length for char".into())),
         }
-        visitor.visit_char(s.chars().next().unwrap())
     }
 
     fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        // Borrowed string deserialization is not supported
-        self.deserialize_string(visitor)
+        // Strings are never borrowed from the input, even a slice, since the
+        // deserializer reads every payload through `R: Read`. The payload is
+        // read into the scratch buffer, and only copied if the visitor needs
+        // an owned string.
+        let header = self.read_header()?;
+        self.visit_text(header, visitor)
     }
 
     fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
@@ -557,25 +1890,71 @@ impl<'de, 'a, R: Read> de::Deserializer<'de> for &'a mut Deserializer<R> {
         V: Visitor<'de>,
     {
         let header = self.read_header()?;
-        visitor.visit_string(self.read_string(header)?)
+        visitor.visit_string(self.read_text(header)?)
     }
 
     fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        let header = self.read_header()?;
+        match header.element_type {
+            ElementType::Text | ElementType::TextRaw
+                if !self.decodes_base64_bytes() =>
+            {
+                self.with_scratch_payload(header, |payload| {
+                    visitor.visit_bytes(payload)
+                })
+            }
+            _ => {
+                self.peeked = Some(header);
+                self.deserialize_byte_buf(visitor)
+            }
+        }
     }
 
+    /// Bytes are stored either as an array of integers, or as text. The
+    /// payload of a `Text` or `TextRaw` element is accepted as is, without
+    /// checking that it is valid UTF-8, unless base64 decoding is enabled.
     fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        let header = self.read_header()?;
+        match header.element_type {
+            ElementType::Array => {}
+            ElementType::Text | ElementType::TextRaw => {
+                let base64 = self.decodes_base64_bytes();
+                let bytes =
+                    self.with_scratch_payload(
+                        header,
+                        |payload| match base64 {
+                            #[cfg(feature = "base64")]
+                            true => crate::base64::decode(payload),
+                            _ => Ok(payload.to_vec()),
+                        },
+                    )?;
+                return visitor.visit_byte_buf(bytes);
+            }
+            t => {
+                return Err(Error::UnexpectedType {
+                    expected: "a text element or Array",
+                    found: t,
+                })
+            }
+        }
+        let bytes = self.in_container(header, |mut de| {
+            let mut bytes = Vec::new();
+            while let Some(byte) = de::SeqAccess::next_element(&mut de)? {
+                bytes.push(byte);
+            }
+            Ok(bytes)
+        })?;
+        visitor.visit_byte_buf(bytes)
     }
 }
 
-impl<'de, 'a, R: Read> de::SeqAccess<'de> for &'a mut Deserializer<R> {
+impl<'de, R: Read> de::SeqAccess<'de> for &mut Deserializer<R> {
     type Error = Error;
 
     fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
@@ -588,15 +1967,42 @@ impl<'de, 'a, R: Read> de::SeqAccess<'de> for &'a mut Deserializer<R> {
             Err(e) => Err(e),
         }
     }
+
+    /// Estimate the number of elements left in the array from the number of
+    /// bytes left in it. Most elements take at least two bytes (a header and
+    /// some payload), so the estimate rarely exceeds twice the actual count.
+    fn size_hint(&self) -> Option<usize> {
+        let end = self.container_end?;
+        let remaining = end - self.position;
+        Some(remaining / 2 + remaining % 2)
+    }
 }
 
-impl<'de, 'a, R: Read> de::MapAccess<'de> for &'a mut Deserializer<R> {
+impl<'de, R: Read> de::MapAccess<'de> for &mut Deserializer<R> {
     type Error = Error;
 
     fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
     where
         K: de::DeserializeSeed<'de>,
     {
+        if self.key_renames.is_some() {
+            let header = match self.read_header() {
+                Ok(header) => header,
+                Err(Error::Empty) => return Ok(None),
+                Err(e) => return Err(e),
+            };
+            if header.element_type.is_text() {
+                let key = self.read_text(header)?;
+                let key = self
+                    .key_renames
+                    .as_ref()
+                    .and_then(|renames| renames.get(&key))
+                    .cloned()
+                    .unwrap_or(key);
+                return seed.deserialize(key.into_deserializer()).map(Some);
+            }
+            self.peeked = Some(header);
+        }
         self.next_element_seed(seed)
     }
 
@@ -605,11 +2011,11 @@ impl<'de, 'a, R: Read> de::MapAccess<'de> for &'a mut Deserializer<R> {
         V: de::DeserializeSeed<'de>,
     {
         self.next_element_seed(seed)
-            .and_then(|opt| opt.ok_or_else(|| Error::Empty))
+            .and_then(|opt| opt.ok_or(Error::MissingObjectValue))
     }
 }
 
-impl<'de, 'a, R: Read> de::EnumAccess<'de> for &'a mut Deserializer<R> {
+impl<'de, R: Read> de::EnumAccess<'de> for &mut Deserializer<R> {
     type Error = Error;
     type Variant = Self;
 
@@ -622,7 +2028,7 @@ impl<'de, 'a, R: Read> de::EnumAccess<'de> for &'a mut Deserializer<R> {
     }
 }
 
-impl<'de, 'a, R: Read> de::VariantAccess<'de> for &'a mut Deserializer<R> {
+impl<'de, R: Read> de::VariantAccess<'de> for &mut Deserializer<R> {
     type Error = Error;
 
     fn unit_variant(self) -> Result<()> {
@@ -654,6 +2060,88 @@ impl<'de, 'a, R: Read> de::VariantAccess<'de> for &'a mut Deserializer<R> {
         de::Deserializer::deserialize_map(self, visitor)
     }
 }
+
+/// The entries of an object, visited as a sequence of two-element tuples.
+struct ObjectPairs<'a, R: Read>(&'a mut Deserializer<R>);
+
+impl<'de, 'a, R: Read> de::SeqAccess<'de> for ObjectPairs<'a, R> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        match self.0.read_header() {
+            Ok(key) => self.0.peeked = Some(key),
+            Err(Error::Empty) => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        seed.deserialize(ObjectPair(&mut *self.0)).map(Some)
+    }
+}
+
+/// A single key and value of an object, deserialized as a tuple.
+struct ObjectPair<'a, R: Read>(&'a mut Deserializer<R>);
+
+impl<'de, 'a, R: Read> de::Deserializer<'de> for ObjectPair<'a, R> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let mut entry = ObjectPairEntry {
+            de: self.0,
+            remaining: 2,
+        };
+        let value = visitor.visit_seq(&mut entry)?;
+        if entry.remaining > 0 {
+            return Err(de::Error::invalid_length(
+                2 - entry.remaining,
+                &"a key and a value",
+            ));
+        }
+        Ok(value)
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
+
+struct ObjectPairEntry<'a, R: Read> {
+    de: &'a mut Deserializer<R>,
+    remaining: usize,
+}
+
+impl<'de, 'a, R: Read> de::SeqAccess<'de> for &mut ObjectPairEntry<'a, R> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: de::DeserializeSeed<'de>,
+    {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        match seed.deserialize(&mut *self.de) {
+            Ok(v) => Ok(Some(v)),
+            // the object ended between a key and its value
+            Err(Error::Empty) => {
+                Err(de::Error::invalid_length(1, &"a key and a value"))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -710,21 +2198,64 @@ mod tests {
         );
     }
 
+    /// Check that `encoded` decodes to `expected` into `T` when it fits,
+    /// and fails with `NumberOutOfRange` otherwise.
+    fn assert_int_type_eq<'a, T>(encoded: &'a [u8], expected: i64)
+    where
+        T: TryFrom<i64> + Deserialize<'a> + PartialEq + std::fmt::Debug,
+    {
+        let decoded = from_slice::<T>(encoded);
+        let target = std::any::type_name::<T>();
+        match T::try_from(expected) {
+            Ok(expected) => assert_eq!(
+                decoded,
+                Ok(expected),
+                "parsing {encoded:?} as {target}"
+            ),
+            Err(_) => assert_eq!(
+                decoded,
+                Err(Error::NumberOutOfRange {
+                    value: expected.to_string(),
+                    target,
+                }),
+                "parsing {encoded:?} as {target}"
+            ),
+        }
+    }
+
     fn assert_all_int_types_eq(encoded: &[u8], expected: i64) {
+        // signed
+        assert_int_type_eq::<i8>(encoded, expected);
+        assert_int_type_eq::<i16>(encoded, expected);
+        assert_int_type_eq::<i32>(encoded, expected);
+        assert_int_type_eq::<i64>(encoded, expected);
         // unsigned
+        assert_int_type_eq::<u8>(encoded, expected);
+        assert_int_type_eq::<u16>(encoded, expected);
+        assert_int_type_eq::<u32>(encoded, expected);
+        assert_int_type_eq::<u64>(encoded, expected);
+    }
+
+    #[test]
+    fn test_narrow_signed_overflow() {
+        let bounds = [
+            (i8::MIN.into(), i8::MAX.into()),
+            (i16::MIN.into(), i16::MAX.into()),
+            (i32::MIN.into(), i32::MAX.into()),
+        ];
+        for (min, max) in bounds {
+            for n in [min - 1, min, max, max + 1] {
+                assert_all_int_types_eq(&crate::to_vec(&n).unwrap(), n);
+            }
+        }
+        assert_all_int_types_eq(b"\x33200", 200);
         assert_eq!(
-            from_slice::<i8>(encoded).unwrap(),
-            expected as i8,
-            "parsing {encoded:?} as i8"
+            from_slice::<i8>(b"\x33200"),
+            Err(Error::NumberOutOfRange {
+                value: "200".into(),
+                target: "i8",
+            })
         );
-        assert_eq!(from_slice::<i16>(encoded).unwrap(), expected as i16);
-        assert_eq!(from_slice::<i32>(encoded).unwrap(), expected as i32);
-        assert_eq!(from_slice::<i64>(encoded).unwrap(), expected);
-        // signed
-        assert_eq!(from_slice::<u8>(encoded).unwrap(), expected as u8);
-        assert_eq!(from_slice::<u16>(encoded).unwrap(), expected as u16);
-        assert_eq!(from_slice::<u32>(encoded).unwrap(), expected as u32);
-        assert_eq!(from_slice::<u64>(encoded).unwrap(), expected as u64);
     }
 
     #[test]
@@ -748,16 +2279,34 @@ mod tests {
     #[test]
     fn test_decoding_large_int() {
         assert_eq!(
-            from_slice::<u64>(b"\xc3\xf418446744073709551615").unwrap(),
+            from_slice::<u64>(b"\xc3\x1418446744073709551615").unwrap(),
             18446744073709551615
         );
         // large negative i64
         assert_eq!(
-            from_slice::<i64>(b"\xc3\xf5-9223372036854775808").unwrap(),
+            from_slice::<i64>(b"\xc3\x14-9223372036854775808").unwrap(),
             -9223372036854775808
         );
     }
 
+    #[test]
+    fn test_over_declared_int_payload() {
+        // ints whose header declares more bytes than the input holds were
+        // once decoded from what was there, but are now reported as
+        // truncated, like any other payload
+        assert_eq!(
+            from_slice::<u64>(b"\xc3\xf418446744073709551615"),
+            Err(Error::PayloadExceedsInput {
+                declared: 0xf4,
+                available: 20,
+            })
+        );
+        assert_eq!(
+            from_reader::<_, i64>(&b"\xc3\xf5-9223372036854775808"[..]),
+            Err(Error::UnexpectedEof)
+        );
+    }
+
     #[test]
     fn test_decoding_large_float() {
         // large negative i64
@@ -785,6 +2334,28 @@ mod tests {
         assert_eq!(from_slice::<Option<u8>>(b"\x2342").unwrap(), Some(42));
     }
 
+    #[test]
+    fn test_option_struct_fields() {
+        #[derive(Debug, PartialEq, serde_derive::Deserialize)]
+        struct Names {
+            name: Option<String>,
+            nick: Option<String>,
+        }
+        let expected = Names {
+            name: None,
+            nick: Some("x".into()),
+        };
+        // {"name": null, "nick": "x"}
+        let bytes = b"\xcc\x0d\x47name\x00\x47nick\x17x";
+        let mut de = Deserializer::from_bytes(bytes);
+        assert_eq!(Names::deserialize(&mut de).unwrap(), expected);
+        assert_eq!(de.position, bytes.len());
+        de.end().unwrap();
+        // {"nick": "x", "name": null}, in an array followed by another value
+        let bytes = b"\xcb\x11\xcc\x0d\x47nick\x17x\x47name\x00\x131";
+        assert_eq!(from_slice::<(Names, u8)>(bytes).unwrap(), (expected, 1));
+    }
+
     #[test]
     fn test_string_noescape() {
         assert_eq!(from_slice::<String>(b"\x57hello").unwrap(), "hello");
@@ -801,6 +2372,77 @@ mod tests {
         assert_eq!(from_slice::<String>(b"\x49\\x0A").unwrap(), "\n");
     }
 
+    #[test]
+    #[cfg(feature = "serde_json5")]
+    fn test_lenient_text() {
+        // a plain Text element that contains an escape anyway
+        let bytes = b"\x87a\\x41\\nb";
+        assert_eq!(from_slice::<String>(bytes).unwrap(), "a\\x41\\nb");
+        let mut de = Deserializer::from_bytes(bytes).with_lenient_text(true);
+        assert_eq!(String::deserialize(&mut de).unwrap(), "aA\nb");
+        #[cfg(feature = "serde_json")]
+        {
+            let mut de =
+                Deserializer::from_bytes(bytes).with_lenient_text(true);
+            assert_eq!(
+                serde_json::Value::deserialize(&mut de).unwrap(),
+                serde_json::Value::from("aA\nb")
+            );
+        }
+    }
+
+    #[test]
+    fn test_lossy_utf8() {
+        let bytes = b"\x3aa\xffb";
+        assert!(from_slice::<String>(bytes).is_err());
+        assert!(from_slice::<&str>(bytes).is_err());
+        let mut de = Deserializer::from_bytes(bytes).with_lossy_utf8(true);
+        assert_eq!(String::deserialize(&mut de).unwrap(), "a\u{fffd}b");
+        let mut de = Deserializer::from_bytes(bytes).with_lossy_utf8(true);
+        assert_eq!(
+            std::borrow::Cow::<str>::deserialize(&mut de).unwrap(),
+            "a\u{fffd}b"
+        );
+        // only TextRaw elements are affected
+        let mut de =
+            Deserializer::from_bytes(b"\x37a\xffb").with_lossy_utf8(true);
+        assert!(String::deserialize(&mut de).is_err());
+    }
+
+    #[test]
+    fn test_max_total_string_bytes() {
+        // ["abc", "de", "f"]
+        let bytes = b"\x9b\x37abc\x27de\x17f";
+        let mut de =
+            Deserializer::from_bytes(bytes).with_max_total_string_bytes(6);
+        assert_eq!(
+            Vec::<String>::deserialize(&mut de).unwrap(),
+            ["abc", "de", "f"]
+        );
+        let mut de =
+            Deserializer::from_bytes(bytes).with_max_total_string_bytes(5);
+        assert_eq!(
+            Vec::<String>::deserialize(&mut de),
+            Err(Error::StringBudgetExceeded(5))
+        );
+        // strings decoded through deserialize_any and object keys count too
+        #[cfg(feature = "serde_json")]
+        {
+            let mut de =
+                Deserializer::from_bytes(bytes).with_max_total_string_bytes(5);
+            assert_eq!(
+                serde_json::Value::deserialize(&mut de),
+                Err(Error::StringBudgetExceeded(5))
+            );
+        }
+        let mut de = Deserializer::from_bytes(b"\x7c\x37abc\x27de")
+            .with_max_total_string_bytes(4);
+        assert_eq!(
+            std::collections::HashMap::<String, String>::deserialize(&mut de),
+            Err(Error::StringBudgetExceeded(4))
+        );
+    }
+
     #[test]
     fn test_tuple() {
         assert_eq!(
@@ -865,6 +2507,40 @@ mod tests {
         assert_eq!(actual, expected);
     }
 
+    #[test]
+    fn test_hashmap_with_custom_hasher() {
+        use std::collections::HashMap;
+        use std::hash::{BuildHasherDefault, Hasher};
+        /// FNV-1a, standing in for the hashers of crates like `fxhash`
+        struct FnvHasher(u64);
+        impl Default for FnvHasher {
+            fn default() -> Self {
+                FnvHasher(0xcbf2_9ce4_8422_2325)
+            }
+        }
+        impl Hasher for FnvHasher {
+            fn finish(&self) -> u64 {
+                self.0
+            }
+            fn write(&mut self, bytes: &[u8]) {
+                for &b in bytes {
+                    self.0 =
+                        (self.0 ^ u64::from(b)).wrapping_mul(0x100_0000_01b3);
+                }
+            }
+        }
+        type FnvHashMap<K, V> = HashMap<K, V, BuildHasherDefault<FnvHasher>>;
+        // {"a": -1, "b": 2}
+        let bytes = b"\x9c\x17a\x23-1\x17b\x132";
+        let actual = from_slice::<FnvHashMap<String, i64>>(bytes).unwrap();
+        let mut expected = FnvHashMap::default();
+        expected.insert("a".to_string(), -1);
+        expected.insert("b".to_string(), 2);
+        assert_eq!(actual, expected);
+        let actual: FnvHashMap<String, i64> = from_reader(&bytes[..]).unwrap();
+        assert_eq!(actual, expected);
+    }
+
     #[test]
     fn test_struct() {
         #[derive(Debug, PartialEq, serde_derive::Deserialize)]
@@ -937,10 +2613,1574 @@ mod tests {
             Y(char),
         }
         assert_eq!(
-            from_slice::<Vec<Test>>(b"\x9b\x8c\x18X\x18Y\x18Y\x18A")
-                .unwrap_err()
-                .to_string(),
-            Error::TrailingCharacters.to_string()
+            from_slice::<Vec<Test>>(b"\x9b\x8c\x18X\x18Y\x18Y\x18A"),
+            Err(Error::TrailingCharacters {
+                remaining: 4,
+                next_element_type: ElementType::TextJ
+            })
+        );
+    }
+
+    #[test]
+    fn test_max_elements() {
+        // an array of 10 nulls is 11 elements
+        let bytes = b"\xab\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
+        let mut de = Deserializer::from_bytes(bytes).with_max_elements(11);
+        assert_eq!(Vec::<()>::deserialize(&mut de).unwrap().len(), 10);
+        let mut de = Deserializer::from_bytes(bytes).with_max_elements(10);
+        assert_eq!(
+            Vec::<()>::deserialize(&mut de),
+            Err(Error::TooManyElements(10))
+        );
+    }
+
+    #[test]
+    fn test_max_elements_counts_nested_elements() {
+        let bytes = b"\xcb\x0a\x4b\x131\x132\x4b\x133\x134";
+        let mut de = Deserializer::from_bytes(bytes).with_max_elements(6);
+        assert_eq!(
+            Vec::<Vec<i16>>::deserialize(&mut de),
+            Err(Error::TooManyElements(6))
+        );
+    }
+
+    #[test]
+    fn test_max_depth() {
+        // [[[]]]
+        let bytes = b"\x2b\x1b\x0b";
+        let mut de = Deserializer::from_bytes(bytes).with_max_depth(3);
+        assert_eq!(
+            Vec::<Vec<Vec<()>>>::deserialize(&mut de),
+            Ok(vec![vec![vec![]]])
+        );
+        let mut de = Deserializer::from_bytes(bytes).with_max_depth(2);
+        assert_eq!(
+            Vec::<Vec<Vec<()>>>::deserialize(&mut de),
+            Err(Error::DepthLimitExceeded(2))
+        );
+    }
+
+    #[test]
+    fn test_ignored_fields_are_skipped() {
+        #[derive(Debug, PartialEq, serde_derive::Deserialize)]
+        struct Test {
+            wanted: u8,
+        }
+        // values that would fail to decode, but whose size is valid
+        let invalid_values: [&[u8]; 3] = [b"\x23xx", b"\x1b\x0d", b"\x2b\x57h"];
+        let mut payload = Vec::new();
+        for i in 0..100 {
+            if i == 50 {
+                payload.extend_from_slice(b"\x67wanted\x137");
+            }
+            payload.extend_from_slice(format!("\x37f{i:02}").as_bytes());
+            payload.extend_from_slice(invalid_values[i % 3]);
+        }
+        let mut bytes = vec![0xdc];
+        bytes.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(&payload);
+        assert_eq!(from_slice::<Test>(&bytes), Ok(Test { wanted: 7 }));
+    }
+
+    #[test]
+    fn test_unexpected_type_message() {
+        assert_eq!(
+            from_slice::<bool>(b"\x131").unwrap_err().to_string(),
+            "unexpected type: expected True or False, found Int"
+        );
+        assert_eq!(
+            from_slice::<()>(b"\x01").unwrap_err().to_string(),
+            "unexpected type: expected Null, found True"
+        );
+        assert_eq!(
+            from_slice::<String>(b"\x0b").unwrap_err().to_string(),
+            "unexpected type: expected a text element, found Array"
+        );
+        assert_eq!(
+            from_slice::<i32>(b"\x17x").unwrap_err().to_string(),
+            "unexpected type: expected Int or Int5, found Text"
+        );
+    }
+
+    #[test]
+    fn test_error_eq() {
+        assert_eq!(
+            from_slice::<bool>(b"\x00"),
+            Err(Error::UnexpectedType {
+                expected: "True or False",
+                found: ElementType::Null,
+            })
+        );
+        assert_eq!(from_slice::<bool>(b""), Err(Error::Empty));
+        assert_eq!(
+            from_slice::<bool>(b"\x01\x01"),
+            Err(Error::TrailingCharacters {
+                remaining: 1,
+                next_element_type: ElementType::True
+            })
+        );
+        assert_eq!(
+            from_reader::<_, String>(&b"\x37a"[..]),
+            Err(Error::UnexpectedEof)
+        );
+        assert_ne!(Error::Empty, Error::TooManyElements(1));
+        assert_ne!(Error::TooManyElements(1), Error::TooManyElements(2));
+    }
+
+    #[test]
+    fn test_heterogeneous_tuple() {
+        // [1, "a", true]
+        let bytes = b"\x5b\x131\x17a\x01";
+        assert_eq!(
+            from_slice::<(u32, String, bool)>(bytes).unwrap(),
+            (1, "a".to_string(), true)
         );
     }
+
+    #[test]
+    fn test_tuple_length_mismatch() {
+        // [1, "a"]
+        assert_eq!(
+            from_slice::<(u32, String, bool)>(b"\x4b\x131\x17a"),
+            Err(Error::Message(
+                "invalid length 2, expected a tuple of size 3".into()
+            ))
+        );
+        // [1, "a", true, null]
+        assert_eq!(
+            from_slice::<(u32, String, bool)>(b"\x6b\x131\x17a\x01\x00"),
+            Err(Error::Message(
+                "invalid length 4, expected an array of length 3".into()
+            ))
+        );
+        // {"a": 1}
+        assert_eq!(
+            from_slice::<(String, u32)>(b"\x4c\x17a\x131"),
+            Err(Error::UnexpectedType {
+                expected: "Array",
+                found: ElementType::Object,
+            })
+        );
+    }
+
+    #[test]
+    fn test_from_bytes_typed() {
+        assert_eq!(
+            from_bytes_typed::<Vec<u8>>(b"\x4b\x131\x132").unwrap(),
+            (ElementType::Array, vec![1, 2])
+        );
+        assert_eq!(
+            from_bytes_typed::<f64>(b"\x131").unwrap(),
+            (ElementType::Int, 1.)
+        );
+        assert_eq!(from_bytes_typed::<()>(b""), Err(Error::Empty));
+    }
+
+    /// Decode `a"b`, encoded with any text element type, through all the
+    /// paths that expect a string.
+    fn assert_text_decodes_everywhere(encoded: &[u8]) {
+        use std::collections::HashMap;
+        #[derive(Debug, PartialEq, serde_derive::Deserialize)]
+        enum Id {
+            #[serde(rename = "a\"b")]
+            Quoted,
+        }
+        let expected = "a\"b";
+        assert_eq!(from_slice::<String>(encoded).unwrap(), expected);
+        assert_eq!(&*from_slice::<Box<str>>(encoded).unwrap(), expected);
+        assert_eq!(from_slice::<Id>(encoded).unwrap(), Id::Quoted);
+        // as a map key: {encoded: null}
+        let mut map_bytes =
+            vec![0xcc, u8::try_from(encoded.len() + 1).unwrap()];
+        map_bytes.extend_from_slice(encoded);
+        map_bytes.push(0x00);
+        let map: HashMap<String, ()> = from_slice(&map_bytes).unwrap();
+        assert_eq!(map.into_keys().collect::<Vec<_>>(), [expected]);
+    }
+
+    #[test]
+    fn test_read_text_element_types() {
+        assert_text_decodes_everywhere(b"\x37a\"b"); // Text
+        assert_text_decodes_everywhere(b"\x48a\\\"b"); // TextJ
+        assert_text_decodes_everywhere(b"\x3aa\"b"); // TextRaw
+    }
+
+    #[test]
+    #[cfg(feature = "serde_json5")]
+    fn test_read_text5() {
+        assert_text_decodes_everywhere(b"\x69a\\x22b"); // Text5
+    }
+
+    #[test]
+    fn test_truncated_header() {
+        for got in [0, 3, 7] {
+            let mut bytes = vec![0xF3];
+            bytes.extend(std::iter::repeat(0).take(got));
+            assert_eq!(
+                from_slice::<u8>(&bytes),
+                Err(Error::TruncatedHeader { expected: 8, got })
+            );
+        }
+        // a complete header with a truncated payload is a different error
+        assert_eq!(
+            from_slice::<u8>(b"\xf3\x00\x00\x00\x00\x00\x00\x00\x01"),
+            Err(Error::PayloadExceedsInput {
+                declared: 1,
+                available: 0
+            })
+        );
+    }
+
+    #[test]
+    fn test_text_payload_is_entire_content() {
+        assert_eq!(from_slice::<String>(b"\x77hello  ").unwrap(), "hello  ");
+        assert_eq!(from_slice::<String>(b"\x78 \\t \\n ").unwrap(), " \t \n ");
+        assert_eq!(from_slice::<String>(b"\xc7\x05hello").unwrap(), "hello");
+        // the next element starts right after the declared payload
+        assert_eq!(
+            from_slice::<(String, u8, String)>(b"\xab\x57hello\x131\x17x")
+                .unwrap(),
+            ("hello".into(), 1, "x".into())
+        );
+        assert_eq!(
+            from_slice::<String>(b"\x57hello!"),
+            Err(Error::TrailingCharacters {
+                remaining: 1,
+                next_element_type: ElementType::from_header_byte(b'!')
+            })
+        );
+    }
+
+    #[test]
+    fn test_element_exceeds_container() {
+        // an array with a 2-byte payload containing a 5-byte string
+        assert_eq!(
+            from_slice::<Vec<String>>(b"\x2b\x57hello"),
+            Err(Error::ElementExceedsContainer)
+        );
+        // the header of the string is cut by the end of the array
+        assert_eq!(
+            from_slice::<Vec<String>>(b"\x1b\xc7\x05hello"),
+            Err(Error::ElementExceedsContainer)
+        );
+        // nested containers
+        assert_eq!(
+            from_slice::<Vec<Vec<u8>>>(b"\x5b\x3b\x131\x132"),
+            Err(Error::ElementExceedsContainer)
+        );
+    }
+
+    #[test]
+    fn test_fixed_size_array() {
+        // [255, 0, 0, 255]
+        let rgba = b"\xcb\x0c\x33255\x130\x130\x33255";
+        assert_eq!(from_slice::<[u8; 4]>(rgba).unwrap(), [255, 0, 0, 255]);
+        assert_eq!(
+            from_slice::<[u8; 3]>(rgba),
+            Err(Error::Message(
+                "invalid length 4, expected an array of length 3".into()
+            ))
+        );
+        assert_eq!(
+            from_slice::<[u8; 5]>(rgba),
+            Err(Error::Message(
+                "invalid length 4, expected an array of length 5".into()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_trailing_characters_context() {
+        let err = from_slice::<u8>(b"\x131\x132").unwrap_err();
+        assert_eq!(
+            err,
+            Error::TrailingCharacters {
+                remaining: 2,
+                next_element_type: ElementType::Int
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "2 trailing bytes after the end of the jsonb value, \
+            starting with an element of type Int"
+        );
+        // readers of unknown length are not read past the first byte
+        assert_eq!(
+            from_reader::<_, u8>(&b"\x131\x0b\x00"[..]),
+            Err(Error::TrailingCharacters {
+                remaining: 1,
+                next_element_type: ElementType::Array
+            })
+        );
+        let mut de = Deserializer::from_reader(EndlessNulls);
+        assert_eq!(<()>::deserialize(&mut de), Ok(()));
+        assert_eq!(
+            de.end(),
+            Err(Error::TrailingCharacters {
+                remaining: 1,
+                next_element_type: ElementType::Null
+            })
+        );
+        let mut de = Deserializer::from_seekable_reader(std::io::Cursor::new(
+            b"\x131\x0b\x00",
+        ))
+        .unwrap();
+        assert_eq!(u8::deserialize(&mut de), Ok(1));
+        assert_eq!(
+            de.end(),
+            Err(Error::TrailingCharacters {
+                remaining: 2,
+                next_element_type: ElementType::Array
+            })
+        );
+    }
+
+    #[test]
+    fn test_trailing_peeked_header() {
+        // 1, followed by a string with a two byte header
+        let bytes = b"\x131\xc7\x03abc";
+        let mut de = Deserializer::from_bytes(bytes);
+        assert_eq!(u8::deserialize(&mut de), Ok(1));
+        let header = de.read_header().unwrap();
+        de.peeked = Some(header);
+        assert_eq!(
+            de.end(),
+            Err(Error::TrailingCharacters {
+                remaining: 5,
+                next_element_type: ElementType::Text
+            })
+        );
+    }
+
+    /// A reader that never ends.
+    struct EndlessNulls;
+
+    impl Read for EndlessNulls {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            buf.fill(0);
+            Ok(buf.len())
+        }
+    }
+
+    #[test]
+    fn test_number_visitor() {
+        /// Keeps the text of any number
+        #[derive(Debug, PartialEq)]
+        struct RawNumber(String);
+        impl<'de> Deserialize<'de> for RawNumber {
+            fn deserialize<D: de::Deserializer<'de>>(
+                d: D,
+            ) -> std::result::Result<Self, D::Error> {
+                struct V;
+                impl<'de> Visitor<'de> for V {
+                    type Value = RawNumber;
+                    fn expecting(
+                        &self,
+                        f: &mut std::fmt::Formatter,
+                    ) -> std::fmt::Result {
+                        f.write_str("a number")
+                    }
+                    fn visit_str<E>(
+                        self,
+                        v: &str,
+                    ) -> std::result::Result<RawNumber, E> {
+                        Ok(RawNumber(v.to_owned()))
+                    }
+                }
+                d.deserialize_any(V)
+            }
+        }
+        // [1, 0.10000000000000000001, 0x1F]
+        let bytes = b"\xcb\x1f\x131\xc5\x160.10000000000000000001\x440x1F";
+        let mut de = Deserializer::from_bytes(bytes).with_number_visitor(
+            |raw, element_type| {
+                Ok(ParsedNumber::String(format!("{element_type:?}:{raw}")))
+            },
+        );
+        assert_eq!(
+            Vec::<RawNumber>::deserialize(&mut de).unwrap(),
+            [
+                RawNumber("Int:1".into()),
+                RawNumber("Float:0.10000000000000000001".into()),
+                RawNumber("Int5:0x1F".into()),
+            ]
+        );
+        let mut de = Deserializer::from_bytes(b"\x131")
+            .with_number_visitor(|_, _| Ok(ParsedNumber::U64(42)));
+        assert_eq!(
+            u64::deserialize(&mut de).unwrap(),
+            1,
+            "not deserialize_any"
+        );
+    }
+
+    /// Encode a payload with every header form that can hold its size
+    fn all_encodings(
+        element_type: ElementType,
+        payload: &[u8],
+    ) -> Vec<Vec<u8>> {
+        let tag = u8::from(element_type);
+        let len = payload.len();
+        let mut headers = vec![];
+        if len <= 11 {
+            headers.push(vec![(len as u8) << 4 | tag]);
+        }
+        if let Ok(len) = u8::try_from(len) {
+            headers.push([&[0xc0 | tag][..], &len.to_be_bytes()].concat());
+        }
+        if let Ok(len) = u16::try_from(len) {
+            headers.push([&[0xd0 | tag][..], &len.to_be_bytes()].concat());
+        }
+        if let Ok(len) = u32::try_from(len) {
+            headers.push([&[0xe0 | tag][..], &len.to_be_bytes()].concat());
+        }
+        headers.push([&[0xf0 | tag][..], &(len as u64).to_be_bytes()].concat());
+        headers
+            .into_iter()
+            .map(|header| [&header[..], payload].concat())
+            .collect()
+    }
+
+    fn assert_all_encodings_eq<T>(
+        element_type: ElementType,
+        payload: &[u8],
+        expected: T,
+    ) where
+        T: de::DeserializeOwned + PartialEq + std::fmt::Debug,
+    {
+        let encodings = all_encodings(element_type, payload);
+        if payload.len() <= 11 {
+            assert_eq!(encodings.len(), 5);
+        }
+        for encoded in encodings {
+            assert_eq!(
+                from_slice::<T>(&encoded).unwrap(),
+                expected,
+                "decoding {encoded:x?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_all_header_encodings() {
+        use std::collections::BTreeMap;
+        assert_all_encodings_eq(ElementType::Int, b"1", 1u8);
+        assert_all_encodings_eq(ElementType::Int, b"-42", -42i64);
+        assert_all_encodings_eq(ElementType::Float, b"1.5", 1.5f64);
+        assert_all_encodings_eq(
+            ElementType::Text,
+            b"hello",
+            "hello".to_string(),
+        );
+        assert_all_encodings_eq(ElementType::TextJ, b"\\n", "\n".to_string());
+        assert_all_encodings_eq(ElementType::TextRaw, b"\"", "\"".to_string());
+        assert_all_encodings_eq(ElementType::Array, b"\x131\x132", vec![1, 2]);
+        assert_all_encodings_eq(ElementType::Array, b"", Vec::<u8>::new());
+        assert_all_encodings_eq(
+            ElementType::Object,
+            b"\x17a\x01",
+            BTreeMap::from([("a".to_string(), true)]),
+        );
+        assert_all_encodings_eq(ElementType::True, b"", true);
+        assert_all_encodings_eq(ElementType::Null, b"", ());
+        // a payload too large for the single byte header
+        let long = "x".repeat(300);
+        assert_all_encodings_eq(
+            ElementType::Text,
+            long.as_bytes(),
+            long.clone(),
+        );
+    }
+
+    #[test]
+    fn test_from_bytes_at() {
+        let buffer = b"\x131\x57hello";
+        let mut de = Deserializer::from_bytes_at(buffer, 2).unwrap();
+        assert_eq!(de.byte_offset(), 2);
+        assert_eq!(String::deserialize(&mut de).unwrap(), "hello");
+        assert_eq!(de.byte_offset(), buffer.len());
+        de.end().unwrap();
+        let mut de = Deserializer::from_bytes_at(buffer, buffer.len()).unwrap();
+        assert_eq!(u8::deserialize(&mut de), Err(Error::Empty));
+        assert!(matches!(
+            Deserializer::from_bytes_at(buffer, buffer.len() + 1),
+            Err(Error::UnexpectedEof)
+        ));
+    }
+
+    /// Records which visitor method `deserialize_any` called for a number
+    #[derive(Debug, PartialEq)]
+    struct VisitedInteger(String);
+
+    impl<'de> Deserialize<'de> for VisitedInteger {
+        fn deserialize<D: de::Deserializer<'de>>(
+            d: D,
+        ) -> std::result::Result<Self, D::Error> {
+            struct V;
+            impl<'de> Visitor<'de> for V {
+                type Value = VisitedInteger;
+                fn expecting(
+                    &self,
+                    f: &mut std::fmt::Formatter,
+                ) -> std::fmt::Result {
+                    f.write_str("an integer")
+                }
+                fn visit_i64<E>(
+                    self,
+                    v: i64,
+                ) -> std::result::Result<VisitedInteger, E> {
+                    Ok(VisitedInteger(format!("i64:{v}")))
+                }
+                fn visit_u64<E>(
+                    self,
+                    v: u64,
+                ) -> std::result::Result<VisitedInteger, E> {
+                    Ok(VisitedInteger(format!("u64:{v}")))
+                }
+            }
+            d.deserialize_any(V)
+        }
+    }
+
+    #[test]
+    fn test_any_int() {
+        let visited =
+            |bytes: &[u8]| from_slice::<VisitedInteger>(bytes).unwrap().0;
+        assert_eq!(visited(b"\x2342"), "i64:42");
+        assert_eq!(visited(b"\x23-7"), "i64:-7");
+        assert_eq!(
+            visited(b"\xc3\x139223372036854775807"),
+            format!("i64:{}", i64::MAX)
+        );
+        assert_eq!(
+            visited(b"\xc3\x1418446744073709551615"),
+            format!("u64:{}", u64::MAX)
+        );
+        assert_eq!(
+            visited(b"\xc3\x14-9223372036854775808"),
+            format!("i64:{}", i64::MIN)
+        );
+    }
+
+    #[test]
+    fn test_any_int_untagged_enum() {
+        #[derive(Debug, PartialEq, serde_derive::Deserialize)]
+        #[serde(untagged)]
+        enum Number {
+            Signed(i64),
+            Unsigned(u64),
+        }
+        // [-1, 42, 9223372036854775808]
+        let bytes = b"\xcb\x1b\x23-1\x2342\xc3\x139223372036854775808";
+        assert_eq!(
+            from_slice::<Vec<Number>>(bytes).unwrap(),
+            [
+                Number::Signed(-1),
+                Number::Signed(42),
+                Number::Unsigned(1 << 63)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_negative_zero() {
+        let f = from_slice::<f64>(b"\x45-0.0").unwrap();
+        assert!(f == 0. && f.is_sign_negative());
+        let f = from_slice::<f32>(b"\x25-0").unwrap();
+        assert!(f == 0. && f.is_sign_negative());
+        // through deserialize_any
+        #[derive(serde_derive::Deserialize)]
+        #[serde(untagged)]
+        enum AnyFloat {
+            Float(f64),
+        }
+        let AnyFloat::Float(f) = from_slice(b"\x45-0.0").unwrap();
+        assert!(f == 0. && f.is_sign_negative());
+        #[cfg(feature = "serde_json5")]
+        {
+            let f = from_slice::<f64>(b"\x36-.0").unwrap();
+            assert!(f == 0. && f.is_sign_negative());
+        }
+    }
+
+    #[test]
+    fn test_any_int5_hex() {
+        // a hex payload is not a valid Int
+        #[cfg(feature = "serde_json")]
+        assert!(from_slice::<VisitedInteger>(b"\x430x1F").is_err());
+        #[cfg(feature = "serde_json5")]
+        assert_eq!(
+            from_slice::<VisitedInteger>(b"\x440x1F").unwrap().0,
+            "i64:31"
+        );
+    }
+
+    #[test]
+    fn test_struct_array_with_long_numbers() {
+        #[derive(Debug, PartialEq, serde_derive::Deserialize)]
+        struct Row {
+            a_long_field_name: u64,
+            b: f64,
+        }
+        // [{"a_long_field_name": 12345678901, "b": 0.123456789}, {"b": 1, ...}]
+        let bytes = b"\xcb\x4a\
+            \xcc\x2d\xc7\x11a_long_field_name\xb312345678901\x17b\xb50.123456789\
+            \xcc\x19\xc7\x11a_long_field_name\x135\x17b\x131";
+        assert_eq!(
+            from_slice::<Vec<Row>>(bytes).unwrap(),
+            [
+                Row {
+                    a_long_field_name: 12345678901,
+                    b: 0.123456789
+                },
+                Row {
+                    a_long_field_name: 5,
+                    b: 1.
+                },
+            ]
+        );
+        // the scratch buffer does not leak the content of a previous key
+        assert_eq!(
+            from_slice::<Vec<String>>(b"\x6b\x37abc\x17d").unwrap(),
+            ["abc", "d"]
+        );
+    }
+
+    #[test]
+    fn test_is_canonical_integer() {
+        assert!(is_canonical_integer(b"0"));
+        assert!(is_canonical_integer(b"-42"));
+        assert!(is_canonical_integer(b"18446744073709551616"));
+        assert!(!is_canonical_integer(b"007"));
+        assert!(!is_canonical_integer(b"+1"));
+        assert!(!is_canonical_integer(b"-"));
+        assert!(!is_canonical_integer(b""));
+        assert!(!is_canonical_integer(b" 1"));
+    }
+
+    #[test]
+    #[cfg(feature = "serde_json")]
+    fn test_any_nested_null_and_bools() {
+        // {"a": null, "b": [true, false]}
+        let bytes = b"\x8c\x17a\x00\x17b\x2b\x01\x02";
+        assert_eq!(
+            from_slice::<serde_json::Value>(bytes).unwrap(),
+            serde_json::json!({"a": null, "b": [true, false]})
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde_json")]
+    fn test_any_nested_mix() {
+        use serde_json::{json, Value};
+        // objects and arrays nested inside each other, alternately
+        let mut value = json!([1, "x", null]);
+        for level in 0..50 {
+            value = if level % 2 == 0 {
+                json!({"level": level, "inner": value, "empty": {}})
+            } else {
+                json!([level, value, [], true])
+            };
+        }
+        let bytes = crate::to_vec(&value).unwrap();
+        assert_eq!(from_slice::<Value>(&bytes).unwrap(), value);
+        assert_eq!(from_reader::<_, Value>(&bytes[..]).unwrap(), value);
+    }
+
+    #[test]
+    fn test_container_type_mismatch() {
+        assert_eq!(
+            from_slice::<Vec<u8>>(b"\x131"),
+            Err(Error::UnexpectedType {
+                expected: "Array or Object",
+                found: ElementType::Int,
+            })
+        );
+        assert_eq!(
+            from_slice::<std::collections::HashMap<String, u8>>(b"\x2b\x131"),
+            Err(Error::UnexpectedType {
+                expected: "Object",
+                found: ElementType::Array,
+            })
+        );
+    }
+
+    #[test]
+    fn test_any_container_boundaries() {
+        // [[1], 2]: the inner array must not swallow the second element
+        #[derive(Debug, PartialEq, serde_derive::Deserialize)]
+        #[serde(untagged)]
+        enum Value {
+            Int(u8),
+            Array(Vec<Value>),
+        }
+        assert_eq!(
+            from_slice::<Value>(b"\x5b\x2b\x131\x132").unwrap(),
+            Value::Array(vec![
+                Value::Array(vec![Value::Int(1)]),
+                Value::Int(2)
+            ])
+        );
+    }
+
+    #[test]
+    fn test_object_as_seq_of_pairs() {
+        // {"b": 1, "a": 2, "b": 3}
+        let bytes = b"\xcc\x0c\x17b\x131\x17a\x132\x17b\x133";
+        assert_eq!(
+            from_slice::<Vec<(String, i64)>>(bytes).unwrap(),
+            [("b".into(), 1), ("a".into(), 2), ("b".into(), 3)]
+        );
+        assert_eq!(from_slice::<Vec<(String, i64)>>(b"\x0c").unwrap(), []);
+        // a key without a value
+        assert_eq!(
+            from_slice::<Vec<(String, i64)>>(b"\x2c\x17b"),
+            Err(Error::Message(
+                "invalid length 1, expected a key and a value".into()
+            ))
+        );
+        // a pair is not a triple
+        assert!(from_slice::<Vec<(String, i64, i64)>>(bytes).is_err());
+    }
+
+    #[test]
+    fn test_root_type() {
+        assert_eq!(root_type(b"\x00"), Ok(ElementType::Null));
+        assert_eq!(root_type(b"\x131"), Ok(ElementType::Int));
+        assert_eq!(root_type(b"\x57hello"), Ok(ElementType::Text));
+        assert_eq!(root_type(b"\xcb\x01\x00"), Ok(ElementType::Array));
+        // the payload is not read, but the header must be complete
+        assert_eq!(root_type(b"\x5c"), Ok(ElementType::Object));
+        assert_eq!(root_type(b"\x0d"), Ok(ElementType::Reserved13));
+        assert_eq!(root_type(b"\x1e0"), Ok(ElementType::Reserved14));
+        assert_eq!(root_type(b"\x5f"), Ok(ElementType::Reserved15));
+        assert_eq!(
+            root_type(b"\xfc"),
+            Err(Error::TruncatedHeader {
+                expected: 8,
+                got: 0
+            })
+        );
+        assert_eq!(root_type(b""), Err(Error::Empty));
+    }
+
+    #[test]
+    fn test_int_with_whitespace() {
+        let bytes = b"\x33 1 ";
+        assert_eq!(
+            from_slice::<i32>(bytes),
+            Err(Error::NonCanonicalInteger(" 1 ".into()))
+        );
+        assert_eq!(
+            from_slice::<i32>(b"\x2301"),
+            Err(Error::NonCanonicalInteger("01".into()))
+        );
+        let mut de = Deserializer::from_bytes(bytes).with_lenient_numbers(true);
+        assert_eq!(i32::deserialize(&mut de), Ok(1));
+        // leading zeros are still rejected in lenient mode
+        let mut de =
+            Deserializer::from_bytes(b"\x43 01 ").with_lenient_numbers(true);
+        assert_eq!(
+            i32::deserialize(&mut de),
+            Err(Error::NonCanonicalInteger("01".into()))
+        );
+    }
+
+    #[test]
+    fn test_int_with_fraction() {
+        let bytes = b"\x331.5";
+        assert_eq!(
+            from_slice::<i32>(bytes),
+            Err(Error::IntegerHasFraction("1.5".into()))
+        );
+        // a float without a fractional part is still not an integer
+        assert_eq!(
+            from_slice::<i32>(b"\x332.0"),
+            Err(Error::NonCanonicalInteger("2.0".into()))
+        );
+        let mut de =
+            Deserializer::from_bytes(bytes).with_coerce_int_float(true);
+        assert_eq!(i32::deserialize(&mut de), Ok(1));
+        let mut de =
+            Deserializer::from_bytes(b"\x43-1.5").with_coerce_int_float(true);
+        assert_eq!(i32::deserialize(&mut de), Ok(-1));
+        let mut de =
+            Deserializer::from_bytes(b"\x332.0").with_coerce_int_float(true);
+        assert_eq!(u8::deserialize(&mut de), Ok(2));
+        // truncated without going through a float
+        let mut de = Deserializer::from_bytes(b"\xc3\x129007199254740993.5")
+            .with_coerce_int_float(true);
+        assert_eq!(u64::deserialize(&mut de), Ok(9007199254740993));
+        // only canonical integers with a fractional part are truncated
+        for (bytes, text) in [
+            (&b"\x43+1.5"[..], "+1.5"),
+            (b"\x53007.0", "007.0"),
+            (b"\x331e2", "1e2"),
+            (b"\x231.", "1."),
+        ] {
+            let mut de =
+                Deserializer::from_bytes(bytes).with_coerce_int_float(true);
+            assert_eq!(
+                i32::deserialize(&mut de),
+                Err(Error::NonCanonicalInteger(text.into()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_reader_owned() {
+        #[derive(Debug, PartialEq, serde_derive::Deserialize)]
+        struct Person {
+            name: String,
+            tags: Vec<String>,
+        }
+        // {"name": "x", "tags": ["a"]}
+        let bytes = b"\xcc\x0f\x47name\x17x\x47tags\x2b\x17a";
+        let expected = Person {
+            name: "x".into(),
+            tags: vec!["a".into()],
+        };
+        let cursor = std::io::Cursor::new(bytes.to_vec());
+        assert_eq!(from_reader::<_, Person>(cursor).unwrap(), expected);
+        let mut de = Deserializer::from_reader(std::io::Cursor::new(bytes));
+        assert_eq!(Person::deserialize(&mut de).unwrap(), expected);
+        de.end().unwrap();
+    }
+
+    #[test]
+    fn test_observer_skipped_value() {
+        #[derive(serde_derive::Deserialize)]
+        struct A {
+            #[allow(dead_code)]
+            a: u8,
+        }
+        // {"a": 1, "b": [1, 2]}
+        let bytes = b"\xbc\x17a\x131\x17b\x4b\x131\x132";
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let observed = Arc::clone(&seen);
+        let mut de =
+            Deserializer::from_bytes(bytes).with_observer(move |header, _| {
+                observed.lock().unwrap().push(header.element_type)
+            });
+        A::deserialize(&mut de).unwrap();
+        // the elements of the skipped array are not reported
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [
+                ElementType::Object,
+                ElementType::Text,
+                ElementType::Int,
+                ElementType::Text,
+                ElementType::Array,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_observer() {
+        use std::sync::{Arc, Mutex};
+        // {"a": [1, true]}
+        let bytes = b"\x6c\x17a\x3b\x131\x01";
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let observed = Arc::clone(&seen);
+        let mut de = Deserializer::from_bytes(bytes).with_observer(
+            move |header, offset| {
+                observed.lock().unwrap().push((
+                    header.element_type,
+                    header.payload_size,
+                    offset,
+                ));
+            },
+        );
+        std::collections::HashMap::<String, (u8, bool)>::deserialize(&mut de)
+            .unwrap();
+        assert_eq!(
+            *seen.lock().unwrap(),
+            [
+                (ElementType::Object, 6, 0),
+                (ElementType::Text, 1, 1),
+                (ElementType::Array, 3, 3),
+                (ElementType::Int, 1, 4),
+                (ElementType::True, 0, 6),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_from_vec() {
+        let bytes = b"\x5b\x17a\x131\x01".to_vec();
+        assert_eq!(
+            from_vec::<(String, u8, bool)>(bytes.clone()),
+            Ok(("a".into(), 1, true))
+        );
+        let mut de = Deserializer::from_vec(bytes);
+        assert_eq!(
+            <(String, u8, bool)>::deserialize(&mut de),
+            Ok(("a".into(), 1, true))
+        );
+        de.end().unwrap();
+        assert!(from_vec::<bool>(b"\x01\x01".to_vec()).is_err());
+    }
+
+    #[test]
+    fn test_from_reader_partial() {
+        let bytes = b"\x131\x2342";
+        let cursor = std::io::Cursor::new(&bytes[..]);
+        let (first, cursor) = from_reader_partial::<_, i32>(cursor).unwrap();
+        assert_eq!(first, 1);
+        assert_eq!(cursor.position(), 2);
+        let (second, cursor) = from_reader_partial::<_, i32>(cursor).unwrap();
+        assert_eq!(second, 42);
+        assert_eq!(
+            from_reader_partial::<_, i32>(cursor).err(),
+            Some(Error::Empty)
+        );
+    }
+
+    #[test]
+    fn test_read_header_borrowed() {
+        // ["hello", 42]
+        let bytes = b"\x9b\x57hello\x2342";
+        let mut de = Deserializer::from_bytes(bytes);
+        let (element_type, payload) = de.read_header_borrowed().unwrap();
+        assert_eq!(element_type, ElementType::Array);
+        assert_eq!(payload.as_ptr(), bytes[1..].as_ptr());
+        assert_eq!(payload.len(), 9);
+        assert_eq!(de.byte_offset(), bytes.len());
+
+        let mut de = Deserializer::from_bytes_at(bytes, 1).unwrap();
+        let (element_type, payload) = de.read_header_borrowed().unwrap();
+        assert_eq!((element_type, payload), (ElementType::Text, &b"hello"[..]));
+        assert_eq!(payload.as_ptr(), bytes[2..].as_ptr());
+        let (element_type, payload) = de.read_header_borrowed().unwrap();
+        assert_eq!((element_type, payload), (ElementType::Int, &b"42"[..]));
+        assert_eq!(de.byte_offset(), bytes.len());
+        de.end().unwrap();
+
+        assert_eq!(
+            Deserializer::from_bytes(b"\x57hell").read_header_borrowed(),
+            Err(Error::PayloadExceedsInput {
+                declared: 5,
+                available: 4
+            })
+        );
+    }
+
+    #[test]
+    fn test_seq_of_options() {
+        // [1, null, 3], then [null, null] with a trailing null
+        assert_eq!(
+            from_slice::<Vec<Option<u8>>>(b"\x5b\x131\x00\x133").unwrap(),
+            [Some(1), None, Some(3)]
+        );
+        assert_eq!(
+            from_slice::<Vec<Option<u8>>>(b"\x2b\x00\x00").unwrap(),
+            [None, None]
+        );
+        // a peeked header is consumed by the option, not read again
+        assert_eq!(
+            from_slice::<Vec<Option<Vec<u8>>>>(b"\x4b\x2b\x131\x00").unwrap(),
+            [Some(vec![1]), None]
+        );
+    }
+
+    #[test]
+    fn test_malformation_errors() {
+        // the payload is cut short
+        let truncated = |declared, available| Error::PayloadExceedsInput {
+            declared,
+            available,
+        };
+        assert_eq!(from_slice::<String>(b"\x57hel"), Err(truncated(5, 3)));
+        assert_eq!(from_slice::<i32>(b"\x23"), Err(truncated(2, 0)));
+        assert_eq!(from_slice::<Vec<u8>>(b"\x3b\x131"), Err(truncated(3, 2)));
+        assert_eq!(
+            from_vec::<String>(b"\x57hel".to_vec()),
+            Err(truncated(5, 3))
+        );
+        // the length of streamed input is not known in advance
+        assert_eq!(
+            from_reader::<_, String>(&b"\x57hel"[..]),
+            Err(Error::UnexpectedEof)
+        );
+        // data after the root element
+        assert_eq!(
+            from_slice::<bool>(b"\x01\x131"),
+            Err(Error::TrailingCharacters {
+                remaining: 2,
+                next_element_type: ElementType::Int
+            })
+        );
+        // an element is larger than the array that contains it
+        assert_eq!(
+            from_slice::<Vec<String>>(b"\x2b\x57hello"),
+            Err(Error::ElementExceedsContainer)
+        );
+        // reserved element types, at the root and nested
+        assert_eq!(
+            from_slice::<()>(b"\x0d"),
+            Err(Error::ReservedElementType(ElementType::Reserved13))
+        );
+        assert_eq!(
+            from_slice::<Vec<()>>(b"\x1b\x0f"),
+            Err(Error::ReservedElementType(ElementType::Reserved15))
+        );
+    }
+
+    #[test]
+    fn test_serde_bytes_from_text() {
+        use serde_bytes::ByteBuf;
+        // a TextRaw element holding bytes that are not valid UTF-8
+        let bytes = b"\x4a\xff\x00\xfe\x80";
+        assert_eq!(
+            from_slice::<ByteBuf>(bytes).unwrap(),
+            ByteBuf::from(vec![0xff, 0x00, 0xfe, 0x80])
+        );
+        assert_eq!(
+            from_slice::<ByteBuf>(b"\x27ab").unwrap(),
+            ByteBuf::from(b"ab".to_vec())
+        );
+        assert_eq!(
+            from_slice::<ByteBuf>(b"\x4b\x131\x132").unwrap(),
+            ByteBuf::from(vec![1, 2])
+        );
+        assert_eq!(
+            from_slice::<ByteBuf>(b"\x131"),
+            Err(Error::UnexpectedType {
+                expected: "a text element or Array",
+                found: ElementType::Int,
+            })
+        );
+        // visit_bytes hands out a slice of the payload
+        struct Len(usize);
+        impl<'de> Deserialize<'de> for Len {
+            fn deserialize<D: de::Deserializer<'de>>(
+                d: D,
+            ) -> std::result::Result<Self, D::Error> {
+                struct V;
+                impl<'de> Visitor<'de> for V {
+                    type Value = Len;
+                    fn expecting(
+                        &self,
+                        f: &mut std::fmt::Formatter,
+                    ) -> std::fmt::Result {
+                        f.write_str("bytes")
+                    }
+                    fn visit_bytes<E>(
+                        self,
+                        v: &[u8],
+                    ) -> std::result::Result<Len, E> {
+                        Ok(Len(v.len()))
+                    }
+                }
+                d.deserialize_bytes(V)
+            }
+        }
+        assert_eq!(from_slice::<Len>(bytes).unwrap().0, 4);
+    }
+
+    #[test]
+    fn test_seq_size_hint() {
+        let values: Vec<u16> = (0..1000).collect();
+        let bytes = crate::to_vec(&values).unwrap();
+        let decoded: Vec<u16> = from_slice(&bytes).unwrap();
+        assert_eq!(decoded, values);
+        // allocated once, without growing, and not much more than needed
+        assert!(decoded.capacity() >= values.len());
+        assert!(decoded.capacity() <= 2 * values.len());
+        // one-byte elements are underestimated, which only costs a regrowth
+        let decoded: Vec<bool> = from_slice(b"\x3b\x01\x02\x01").unwrap();
+        assert_eq!(decoded, [true, false, true]);
+    }
+
+    #[test]
+    fn test_strict_empty_payloads() {
+        // a True element with a 1-byte payload
+        let bytes = b"\x11\x00";
+        assert_eq!(from_slice::<bool>(bytes), Ok(true));
+        let mut de =
+            Deserializer::from_bytes(bytes).with_strict_empty_payloads(true);
+        assert_eq!(
+            bool::deserialize(&mut de),
+            Err(Error::UnexpectedPayload(ElementType::True))
+        );
+        // [null] with a 1-byte payload on the null
+        let bytes = b"\x2b\x10\x00";
+        let mut de =
+            Deserializer::from_bytes(bytes).with_strict_empty_payloads(true);
+        assert_eq!(
+            <Vec<()>>::deserialize(&mut de),
+            Err(Error::UnexpectedPayload(ElementType::Null))
+        );
+        let mut de =
+            Deserializer::from_bytes(b"\x02").with_strict_empty_payloads(true);
+        assert_eq!(bool::deserialize(&mut de), Ok(false));
+        // a wrong type with a payload is reported as such
+        let mut de =
+            Deserializer::from_bytes(b"\x131").with_strict_empty_payloads(true);
+        assert_eq!(
+            <()>::deserialize(&mut de),
+            Err(Error::UnexpectedType {
+                expected: "Null",
+                found: ElementType::Int,
+            })
+        );
+        let mut de =
+            Deserializer::from_bytes(b"\x131").with_strict_empty_payloads(true);
+        assert_eq!(
+            bool::deserialize(&mut de),
+            Err(Error::UnexpectedType {
+                expected: "True or False",
+                found: ElementType::Int,
+            })
+        );
+    }
+
+    #[test]
+    fn test_ignored_any_validates() {
+        use serde::de::IgnoredAny;
+        // {"a": [1, {"b": null}], "c": "x"}
+        let bytes = b"\xcc\x0d\x1aa\x6b\x131\x3c\x1ab\x00\x1ac\x1ax";
+        assert_eq!(from_slice::<IgnoredAny>(bytes), Ok(IgnoredAny));
+        let mut trailing = bytes.to_vec();
+        trailing.push(0x13);
+        assert_eq!(
+            from_slice::<IgnoredAny>(&trailing),
+            Err(Error::TrailingCharacters {
+                remaining: 1,
+                next_element_type: ElementType::Int,
+            })
+        );
+        // [[1]], where the integer claims a payload larger than its array
+        assert_eq!(
+            from_slice::<IgnoredAny>(b"\x3b\x2b\x231"),
+            Err(Error::ElementExceedsContainer)
+        );
+        // an object with a key and no value
+        assert_eq!(
+            from_slice::<IgnoredAny>(b"\x2c\x1aa"),
+            Err(Error::MissingObjectValue)
+        );
+    }
+
+    #[test]
+    fn test_max_input_bytes() {
+        // ["abcd", "efgh"]
+        let bytes = b"\xab\x4aabcd\x4aefgh";
+        let mut de = Deserializer::from_bytes(bytes).with_max_input_bytes(11);
+        assert_eq!(
+            <Vec<String>>::deserialize(&mut de),
+            Ok(vec!["abcd".into(), "efgh".into()])
+        );
+        // the limit is reached in the middle of the second string
+        let mut de = Deserializer::from_bytes(bytes).with_max_input_bytes(8);
+        assert_eq!(
+            <Vec<String>>::deserialize(&mut de),
+            Err(Error::InputTooLarge(8))
+        );
+        let mut de =
+            Deserializer::from_reader(&bytes[..]).with_max_input_bytes(8);
+        assert_eq!(
+            <Vec<String>>::deserialize(&mut de),
+            Err(Error::InputTooLarge(8))
+        );
+        // borrowed payloads count too
+        let mut de =
+            Deserializer::from_bytes(b"\x4aabcd").with_max_input_bytes(3);
+        assert_eq!(de.read_header_borrowed(), Err(Error::InputTooLarge(3)));
+    }
+
+    #[test]
+    fn test_max_input_bytes_oversized_text() {
+        // a text element that claims to be 64 GiB long, in 13 bytes
+        let bytes = b"\xf7\x00\x00\x00\x10\x00\x00\x00\x00abc";
+        let mut de =
+            Deserializer::from_reader(&bytes[..]).with_max_input_bytes(1024);
+        assert_eq!(
+            String::deserialize(&mut de),
+            Err(Error::InputTooLarge(1024))
+        );
+        let mut de =
+            Deserializer::from_reader(&bytes[..]).with_max_input_bytes(1024);
+        assert_eq!(
+            serde::de::IgnoredAny::deserialize(&mut de).map(drop),
+            Err(Error::InputTooLarge(1024))
+        );
+        // without a limit, only the bytes that are there are read
+        let mut de = Deserializer::from_reader(&bytes[..]);
+        assert_eq!(String::deserialize(&mut de), Err(Error::UnexpectedEof));
+    }
+
+    #[test]
+    fn test_tuple_struct_point() {
+        #[derive(Debug, PartialEq, serde_derive::Deserialize)]
+        struct Point(f64, f64);
+        // [1.5, -2.5]
+        let bytes = b"\x9b\x351.5\x45-2.5";
+        assert_eq!(from_slice::<Point>(bytes), Ok(Point(1.5, -2.5)));
+        // [1.5]
+        assert_eq!(
+            from_slice::<Point>(b"\x4b\x351.5").unwrap_err().to_string(),
+            "invalid length 1, expected tuple struct Point with 2 elements"
+        );
+        // [1.5, -2.5, 0.0]
+        assert_eq!(
+            from_slice::<Point>(b"\xdb\x00\x0d\x351.5\x45-2.5\x350.0")
+                .unwrap_err()
+                .to_string(),
+            "invalid length 3, expected an array of length 2"
+        );
+        // [1.5, "x"]
+        assert!(from_slice::<Point>(b"\x6b\x351.5\x17x").is_err());
+        // {"0": 1.5, "1": -2.5} is not accepted in place of an array
+        assert_eq!(
+            from_slice::<Point>(b"\xbc\x170\x351.5\x171\x45-2.5"),
+            Err(Error::UnexpectedType {
+                expected: "Array",
+                found: ElementType::Object,
+            })
+        );
+    }
+
+    #[test]
+    fn test_integer_out_of_range() {
+        fn out_of_range(value: &str, target: &'static str) -> Error {
+            Error::NumberOutOfRange {
+                value: value.into(),
+                target,
+            }
+        }
+        let int = |text: &str| {
+            let mut bytes = vec![0xc3, text.len() as u8];
+            bytes.extend_from_slice(text.as_bytes());
+            bytes
+        };
+        assert_eq!(from_slice::<i8>(&int("127")), Ok(127));
+        assert_eq!(
+            from_slice::<i8>(&int("128")),
+            Err(out_of_range("128", "i8"))
+        );
+        assert_eq!(
+            from_slice::<i8>(&int("-129")),
+            Err(out_of_range("-129", "i8"))
+        );
+        assert_eq!(
+            from_slice::<u8>(&int("256")),
+            Err(out_of_range("256", "u8"))
+        );
+        assert_eq!(from_slice::<u8>(&int("-1")), Err(out_of_range("-1", "u8")));
+        let above_i64 = "9223372036854775808";
+        assert_eq!(
+            from_slice::<i64>(&int(above_i64)),
+            Err(out_of_range(above_i64, "i64"))
+        );
+        assert_eq!(from_slice::<u64>(&int(above_i64)), Ok(1 << 63));
+        let above_u64 = "18446744073709551616";
+        assert_eq!(
+            from_slice::<u64>(&int(above_u64)),
+            Err(out_of_range(above_u64, "u64"))
+        );
+        assert_eq!(
+            from_slice::<u64>(&int("-1")),
+            Err(out_of_range("-1", "u64"))
+        );
+        let huge = "1".repeat(50);
+        assert_eq!(
+            from_slice::<u128>(&int(&huge)),
+            Err(out_of_range(&huge, "i128 or u128"))
+        );
+        assert_eq!(
+            from_slice::<u8>(&int("256")).unwrap_err().to_string(),
+            "256 is out of the range of u8"
+        );
+    }
+
+    #[test]
+    fn test_reset() {
+        #[derive(Debug, PartialEq, serde_derive::Deserialize)]
+        struct Row {
+            id: u8,
+        }
+        // a limit that each row fits in, but not both together
+        let mut de = Deserializer::from_bytes(b"\x5c\x27id\x131")
+            .with_max_total_string_bytes(2);
+        assert_eq!(Row::deserialize(&mut de), Ok(Row { id: 1 }));
+        de.end().unwrap();
+        de.reset(b"\x5c\x27id\x132");
+        assert_eq!(de.byte_offset(), 0);
+        assert_eq!(Row::deserialize(&mut de), Ok(Row { id: 2 }));
+        de.end().unwrap();
+        // an error does not leave any state behind
+        de.reset(b"\x2b\x131");
+        assert!(Row::deserialize(&mut de).is_err());
+        de.reset(b"\x5c\x27id\x133");
+        assert_eq!(Row::deserialize(&mut de), Ok(Row { id: 3 }));
+        de.end().unwrap();
+    }
+
+    #[test]
+    fn test_key_rename() {
+        #[derive(Debug, PartialEq, serde_derive::Deserialize)]
+        #[allow(non_snake_case)]
+        struct User {
+            userId: u32,
+            name: String,
+        }
+        // {"user_id": 7, "name": "a"}
+        let bytes = b"\xcc\x11\x7auser_id\x137\x4aname\x17a";
+        let renames = HashMap::from([("user_id".into(), "userId".into())]);
+        let mut de = Deserializer::from_bytes(bytes).with_key_rename(renames);
+        let user = User::deserialize(&mut de).unwrap();
+        assert_eq!(
+            user,
+            User {
+                userId: 7,
+                name: "a".into()
+            }
+        );
+        de.end().unwrap();
+        assert!(crate::from_slice::<User>(bytes).is_err());
+    }
+
+    #[cfg(feature = "serde_json5")]
+    #[test]
+    fn test_non_finite_floats() {
+        for bytes in [&b"\x86Infinity"[..], b"\x96-Infinity", b"\x36NaN"] {
+            assert_eq!(
+                crate::from_slice::<f64>(bytes),
+                Err(Error::NonFiniteFloat)
+            );
+            assert_eq!(
+                crate::from_slice::<f32>(bytes),
+                Err(Error::NonFiniteFloat)
+            );
+            let mut de =
+                Deserializer::from_bytes(bytes).with_allow_non_finite(true);
+            assert!(!f64::deserialize(&mut de).unwrap().is_finite());
+        }
+        let mut de = Deserializer::from_bytes(b"\x86Infinity")
+            .with_allow_non_finite(true);
+        assert_eq!(f32::deserialize(&mut de), Ok(f32::INFINITY));
+        assert_eq!(crate::from_slice::<f64>(b"\x26.5"), Ok(0.5));
+    }
+
+    #[test]
+    fn test_allow_trailing_zeros() {
+        let padded = b"\x131\x00\x00\x00";
+        let mut de =
+            Deserializer::from_bytes(padded).with_allow_trailing_zeros(true);
+        assert_eq!(u8::deserialize(&mut de), Ok(1));
+        de.end().unwrap();
+        assert_eq!(
+            crate::from_slice::<u8>(padded),
+            Err(Error::TrailingCharacters {
+                remaining: 3,
+                next_element_type: ElementType::Null,
+            })
+        );
+        let mut de = Deserializer::from_bytes(b"\x131\x00\x00\x131\x00")
+            .with_allow_trailing_zeros(true);
+        assert_eq!(u8::deserialize(&mut de), Ok(1));
+        assert_eq!(
+            de.end(),
+            Err(Error::TrailingCharacters {
+                remaining: 3,
+                next_element_type: ElementType::Int,
+            })
+        );
+    }
+
+    #[test]
+    fn test_seekable_reader_skips_payloads() {
+        use std::io::Cursor;
+
+        /// Counts the bytes actually read from a cursor.
+        struct CountingCursor {
+            cursor: Cursor<Vec<u8>>,
+            read: usize,
+        }
+        impl Read for CountingCursor {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let n = self.cursor.read(buf)?;
+                self.read += n;
+                Ok(n)
+            }
+        }
+        impl Seek for CountingCursor {
+            fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+                self.cursor.seek(pos)
+            }
+        }
+
+        #[derive(Debug, serde_derive::Deserialize)]
+        struct Small {
+            a: u8,
+        }
+        // {"big": "x" * 1000, "a": 1}, then 2
+        let mut bytes = b"\xdc\x03\xf3\x3abig\xdc\x03\xe8".to_vec();
+        bytes.extend_from_slice(&[b'x'; 1000]);
+        bytes.extend_from_slice(b"\x1aa\x131\x132");
+        let reader = CountingCursor {
+            cursor: Cursor::new(bytes),
+            read: 0,
+        };
+        let mut de = Deserializer::from_seekable_reader(reader).unwrap();
+        assert_eq!(Small::deserialize(&mut de).unwrap().a, 1);
+        assert_eq!(de.byte_offset(), 1014);
+        assert_eq!(u8::deserialize(&mut de), Ok(2));
+        de.end().unwrap();
+        let reader = de.reader.into_inner();
+        assert!(reader.read < 100, "{} bytes read", reader.read);
+
+        // a payload that extends past the end is not seeked over
+        let mut de =
+            Deserializer::from_seekable_reader(Cursor::new(b"\x5b\x131"))
+                .unwrap();
+        assert_eq!(
+            serde::de::IgnoredAny::deserialize(&mut de),
+            Err(Error::PayloadExceedsInput {
+                declared: 5,
+                available: 2,
+            })
+        );
+    }
+
+    #[test]
+    fn test_object_key_without_value() {
+        #[derive(Debug, serde_derive::Deserialize)]
+        struct Pair {
+            #[allow(dead_code)]
+            a: u8,
+        }
+        // {"a": 1, "b"}
+        let bytes = b"\x6c\x17a\x131\x17b";
+        assert_eq!(
+            from_slice::<HashMap<String, u8>>(bytes),
+            Err(Error::MissingObjectValue)
+        );
+        // {"a"}
+        assert_eq!(
+            from_slice::<Pair>(b"\x2c\x17a").unwrap_err(),
+            Error::MissingObjectValue
+        );
+        assert_eq!(
+            from_reader::<_, HashMap<String, u8>>(&bytes[..]),
+            Err(Error::MissingObjectValue)
+        );
+    }
+
+    #[test]
+    fn test_lenient_bool() {
+        let lenient = |bytes| {
+            bool::deserialize(
+                &mut Deserializer::from_bytes(bytes).with_lenient_bool(true),
+            )
+        };
+        assert_eq!(lenient(b"\x47true"), Ok(true));
+        assert_eq!(lenient(b"\x5afalse"), Ok(false));
+        assert_eq!(lenient(b"\x131"), Ok(true));
+        assert_eq!(lenient(b"\x130"), Ok(false));
+        assert_eq!(lenient(b"\x01"), Ok(true));
+        assert!(lenient(b"\x37yes").is_err());
+        assert!(lenient(b"\x47True").is_err());
+        assert!(lenient(b"\x132").is_err());
+        assert!(lenient(b"\x25true").is_err());
+        assert!(lenient(b"\x43true").is_err());
+        assert!(lenient(b"\x53false").is_err());
+        assert_eq!(
+            from_slice::<bool>(b"\x47true"),
+            Err(Error::UnexpectedType {
+                expected: "True or False",
+                found: ElementType::Text,
+            })
+        );
+    }
+
+    #[test]
+    fn test_on_reserved() {
+        #[derive(Debug, PartialEq, serde_derive::Deserialize)]
+        #[serde(untagged)]
+        enum Any {
+            Null,
+            Int(i64),
+            List(Vec<Any>),
+        }
+        // [1, <Reserved14 with payload "x">, 2]
+        let bytes = b"\x6b\x131\x1ex\x132";
+        let decode = |on_reserved| {
+            Any::deserialize(
+                &mut Deserializer::from_bytes(bytes)
+                    .with_on_reserved(on_reserved),
+            )
+        };
+        assert!(decode(OnReserved::default()).is_err());
+        assert_eq!(
+            decode(OnReserved::SkipAsNull),
+            Ok(Any::List(vec![Any::Int(1), Any::Null, Any::Int(2)]))
+        );
+        let tag = |t: ElementType, payload: &[u8]| {
+            assert_eq!(payload, b"x");
+            Ok(format!("\x13{}", u8::from(t) % 10).into_bytes())
+        };
+        assert_eq!(
+            decode(OnReserved::Custom(tag)),
+            Ok(Any::List(vec![Any::Int(1), Any::Int(4), Any::Int(2)]))
+        );
+        let fail = |t, _: &[u8]| Err(Error::ReservedElementType(t));
+        assert_eq!(
+            from_slice::<Vec<u8>>(bytes),
+            Err(Error::ReservedElementType(ElementType::Reserved14))
+        );
+        assert!(decode(OnReserved::Custom(fail)).is_err());
+        // typed methods still reject reserved types
+        let mut de = Deserializer::from_bytes(bytes)
+            .with_on_reserved(OnReserved::SkipAsNull);
+        assert_eq!(
+            Vec::<Option<u8>>::deserialize(&mut de),
+            Err(Error::ReservedElementType(ElementType::Reserved14))
+        );
+    }
+
+    #[cfg(feature = "serde_json5")]
+    #[test]
+    fn test_int5_negative_hex() {
+        assert_eq!(from_slice::<i64>(b"\x54-0x10"), Ok(-16));
+        assert_eq!(from_slice::<i8>(b"\x54-0x80"), Ok(-128));
+        assert_eq!(from_slice::<i16>(b"\x54-0xFF"), Ok(-255));
+        assert_eq!(
+            from_slice::<i8>(b"\x54-0x81"),
+            Err(Error::NumberOutOfRange {
+                value: "-129".into(),
+                target: "i8",
+            })
+        );
+        assert!(from_slice::<u8>(b"\x44-0x1").is_err());
+        assert_eq!(
+            from_slice::<VisitedInteger>(b"\x54-0x10").unwrap().0,
+            "i64:-16"
+        );
+    }
+
+    #[test]
+    fn test_cancel_flag() {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let bytes = crate::to_vec(&vec![1u8; 10_000]).unwrap();
+        let mut de =
+            Deserializer::from_bytes(&bytes).with_cancel_flag(cancel.clone());
+        assert_eq!(Vec::<u8>::deserialize(&mut de).unwrap().len(), 10_000);
+
+        // cancel after the 100th element has been decoded
+        let flag = cancel.clone();
+        let mut seen = 0;
+        let mut de = Deserializer::from_bytes(&bytes)
+            .with_cancel_flag(cancel)
+            .with_observer(move |_, _| {
+                seen += 1;
+                if seen == 100 {
+                    flag.store(true, Ordering::Relaxed);
+                }
+            });
+        assert_eq!(Vec::<u8>::deserialize(&mut de), Err(Error::Cancelled));
+        assert!(de.position < bytes.len() / 10);
+    }
 }
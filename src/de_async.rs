@@ -0,0 +1,108 @@
+//! Decoding of JSONB data from asynchronous readers.
+
+use crate::error::{Error, Result};
+use crate::header::{ElementType, Header};
+use serde::de::DeserializeOwned;
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Deserialize an instance of type `T` from an asynchronous reader of
+/// SQLite JSONB data.
+///
+/// serde deserializers are synchronous, so the root element is first read
+/// into memory (its size is known from its header), then decoded.
+///
+/// The reader is not read past the first byte that follows the value, so
+/// the `remaining` count of [`Error::TrailingCharacters`] is always 1, a
+/// lower bound of the number of trailing bytes.
+pub async fn from_async_reader<R, T>(mut reader: R) -> Result<T>
+where
+    R: AsyncRead + Unpin,
+    T: DeserializeOwned,
+{
+    let element = read_element(&mut reader).await?;
+    let t = crate::from_slice(&element)?;
+    let mut first_byte = [0u8];
+    if reader.read(&mut first_byte).await? == 0 {
+        return Ok(t);
+    }
+    // the length of the input is unknown, and the rest of it is not read
+    Err(Error::TrailingCharacters {
+        remaining: 1,
+        next_element_type: ElementType::from_header_byte(first_byte[0]),
+    })
+}
+
+/// Read the header and payload of a single element.
+async fn read_element<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Vec<u8>> {
+    let mut element = vec![0u8; 1];
+    if reader.read(&mut element).await? == 0 {
+        return Err(Error::Empty);
+    }
+    let size_len = Header::size_field_len(element[0]);
+    element.resize(1 + size_len, 0);
+    let got = read_fully(reader, &mut element[1..]).await?;
+    if got < size_len {
+        return Err(Error::TruncatedHeader {
+            expected: size_len,
+            got,
+        });
+    }
+    let header = Header::from_parts(element[0], &element[1..]);
+    let payload_size = u64::try_from(header.payload_size)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    // The buffer grows with the data actually read,
+    // not with the size the header claims.
+    let read = reader.take(payload_size).read_to_end(&mut element).await?;
+    if read != header.payload_size {
+        return Err(Error::UnexpectedEof);
+    }
+    Ok(element)
+}
+
+async fn read_fully<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    buf: &mut [u8],
+) -> Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]).await? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    Ok(read)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_from_async_reader() {
+        let bytes: &[u8] = b"\xcb\x0a\x4b\x131\x132\x4b\x133\x134";
+        let v: Vec<Vec<i16>> = from_async_reader(bytes).await.unwrap();
+        assert_eq!(v, vec![vec![1, 2], vec![3, 4]]);
+    }
+
+    #[tokio::test]
+    async fn test_from_async_reader_errors() {
+        let trailing: &[u8] = b"\x131\x131";
+        assert_eq!(
+            from_async_reader::<_, u8>(trailing).await,
+            Err(Error::TrailingCharacters {
+                remaining: 1,
+                next_element_type: ElementType::Int
+            })
+        );
+        let truncated: &[u8] = b"\xf3\x00";
+        assert_eq!(
+            from_async_reader::<_, u8>(truncated).await,
+            Err(Error::TruncatedHeader {
+                expected: 8,
+                got: 1
+            })
+        );
+        let empty: &[u8] = b"";
+        assert_eq!(from_async_reader::<_, u8>(empty).await, Err(Error::Empty));
+    }
+}
@@ -0,0 +1,73 @@
+//! (De)serialization of [`Decimal`]s through the exact text of number
+//! elements, for use with `#[serde(with = "...")]`.
+//!
+//! ```
+//! use rust_decimal::Decimal;
+//!
+//! #[derive(serde::Deserialize)]
+//! struct Price {
+//!     #[serde(with = "serde_sqlite_jsonb::decimal")]
+//!     amount: Decimal,
+//! }
+//! ```
+//!
+//! Decimals are read from the text of `Int` and `Float` elements, as with
+//! [`number_text`](crate::number_text), so they keep their exact value and
+//! scale: `1.10` is decoded with two decimal places, and encoded back as
+//! `1.10`. This module requires the `rust_decimal` feature.
+
+use rust_decimal::Decimal;
+use serde::de::Deserializer;
+use serde::ser::Serializer;
+
+/// Serialize a decimal as a number element containing its text, as an `Int`
+/// element if it has no decimal places, and as a `Float` element otherwise.
+pub fn serialize<S: Serializer>(
+    decimal: &Decimal,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    crate::number_text::serialize(decimal, serializer)
+}
+
+/// Deserialize a decimal from the text of an `Int` or `Float` element.
+pub fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Decimal, D::Error> {
+    crate::number_text::deserialize(deserializer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(
+        Debug, PartialEq, serde_derive::Serialize, serde_derive::Deserialize,
+    )]
+    struct Price {
+        #[serde(with = "super")]
+        amount: Decimal,
+    }
+
+    #[test]
+    fn test_decimal_keeps_scale() {
+        // {"amount": 1.10}
+        let encoded = b"\xcc\x0c\x6aamount\x451.10";
+        let decoded = crate::from_slice::<Price>(encoded).unwrap();
+        assert_eq!(decoded.amount, Decimal::new(110, 2));
+        assert_eq!(decoded.amount.scale(), 2);
+        assert_eq!(crate::to_vec(&decoded).unwrap(), encoded);
+    }
+
+    #[test]
+    fn test_decimal_from_int() {
+        // {"amount": 42}
+        let encoded = b"\xac\x6aamount\x2342";
+        let decoded = crate::from_slice::<Price>(encoded).unwrap();
+        assert_eq!(decoded.amount, Decimal::new(42, 0));
+        assert_eq!(crate::to_vec(&decoded).unwrap(), encoded);
+        // {"amount": 0x2A}
+        assert!(
+            crate::from_slice::<Price>(b"\xcc\x0c\x6aamount\x440x2A").is_err()
+        );
+    }
+}
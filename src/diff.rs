@@ -0,0 +1,273 @@
+//! Structural comparison of two JSONB values.
+
+use crate::error::{Error, Result};
+use crate::header::ElementType;
+use crate::raw::RawElement;
+use std::collections::{HashMap, HashSet};
+
+/// A step in the path from the root of a value to one of its elements.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PathSegment {
+    /// The value associated with a key in an object
+    Key(String),
+    /// The element at an index in an array
+    Index(usize),
+}
+
+/// A difference between two JSONB values, located by the path to the
+/// element that differs.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DiffOp {
+    /// The key or index is only present in the new value.
+    Added(Vec<PathSegment>),
+    /// The key or index is only present in the old value.
+    Removed(Vec<PathSegment>),
+    /// The element was replaced by a different one.
+    Changed(Vec<PathSegment>),
+}
+
+/// An item of work of [`diff`]: either a pair of elements left to compare,
+/// or a difference that was found, waiting for its turn to be reported.
+enum Step<'a, 'b> {
+    Compare(Vec<PathSegment>, RawElement<'a>, RawElement<'b>),
+    Report(DiffOp),
+}
+
+/// List the differences between two JSONB values.
+///
+/// Objects are compared key by key, without regard to the order of their
+/// keys, and arrays index by index. Scalars are equal when they have the
+/// same type and payload, except for strings, which are compared after
+/// decoding, whatever their text type, and numbers, which are compared by
+/// value: integers (`Int` and `Int5`) with integers, and floats (`Float` and
+/// `Float5`) with floats. If an object contains a key several times, only
+/// its first occurrence is used.
+///
+/// Differences are reported in the order in which they appear in the
+/// documents. Identical subtrees are skipped without being traversed.
+pub fn diff(old: &[u8], new: &[u8]) -> Result<Vec<DiffOp>> {
+    let old = RawElement::read_root(old)?;
+    let new = RawElement::read_root(new)?;
+    let mut ops = Vec::new();
+    // an explicit stack, so that deeply nested input cannot overflow the
+    // call stack
+    let mut stack = vec![Step::Compare(Vec::new(), old, new)];
+    while let Some(step) = stack.pop() {
+        let (path, old, new) = match step {
+            Step::Report(op) => {
+                ops.push(op);
+                continue;
+            }
+            Step::Compare(path, old, new) => (path, old, new),
+        };
+        let steps = match (old.element_type(), new.element_type()) {
+            _ if old.bytes == new.bytes => continue,
+            (ElementType::Object, ElementType::Object) => {
+                diff_objects(&path, old, new)?
+            }
+            (ElementType::Array, ElementType::Array) => {
+                diff_arrays(&path, old, new)?
+            }
+            _ if scalars_equal(old, new)? => continue,
+            _ => vec![Step::Report(DiffOp::Changed(path))],
+        };
+        stack.extend(steps.into_iter().rev());
+    }
+    Ok(ops)
+}
+
+/// Check whether two JSONB values are equal, whatever the way they are
+/// encoded: the size of their headers, the type of their text elements,
+/// the format of their numbers, and the order of the keys of their objects
+/// do not matter. Equality is defined as for [`diff`].
+pub fn semantic_eq(a: &[u8], b: &[u8]) -> Result<bool> {
+    Ok(diff(a, b)?.is_empty())
+}
+
+fn scalars_equal(old: RawElement, new: RawElement) -> Result<bool> {
+    let (old_type, new_type) = (old.element_type(), new.element_type());
+    if old_type.is_text() && new_type.is_text() {
+        return Ok(old.decode::<String>()? == new.decode::<String>()?);
+    }
+    let is_integer = |t| matches!(t, ElementType::Int | ElementType::Int5);
+    let is_float = |t| matches!(t, ElementType::Float | ElementType::Float5);
+    if is_integer(old_type) && is_integer(new_type) {
+        if let (Some(o), Some(n)) = (integer_value(old), integer_value(new)) {
+            return Ok(o == n);
+        }
+    }
+    if is_float(old_type) && is_float(new_type) {
+        if let (Ok(o), Ok(n)) = (old.decode::<f64>(), new.decode::<f64>()) {
+            return Ok(o == n);
+        }
+    }
+    // payloads that cannot be parsed are compared as they are
+    Ok(old_type == new_type && old.payload() == new.payload())
+}
+
+/// The value of an integer element, as an `i128`, or as a `u128` for
+/// integers above `i128::MAX`.
+fn integer_value(element: RawElement) -> Option<(i128, u128)> {
+    match element.decode::<i128>() {
+        Ok(i) => Some((i, 0)),
+        Err(_) => element.decode::<u128>().ok().map(|u| (i128::MAX, u)),
+    }
+}
+
+fn child_path(path: &[PathSegment], segment: PathSegment) -> Vec<PathSegment> {
+    let mut path = path.to_vec();
+    path.push(segment);
+    path
+}
+
+/// The entries of an object, in order, with their keys decoded.
+fn object_entries(object: RawElement) -> Result<Vec<(String, RawElement)>> {
+    let mut entries = Vec::new();
+    let mut children = object.children();
+    while let Some(key) = children.next().transpose()? {
+        let value = children
+            .next()
+            .transpose()?
+            .ok_or(Error::MissingObjectValue)?;
+        entries.push((key.decode::<String>()?, value));
+    }
+    Ok(entries)
+}
+
+fn diff_objects<'a, 'b>(
+    path: &[PathSegment],
+    old: RawElement<'a>,
+    new: RawElement<'b>,
+) -> Result<Vec<Step<'a, 'b>>> {
+    let old_entries = object_entries(old)?;
+    let new_entries = object_entries(new)?;
+    let mut new_values = HashMap::new();
+    for (key, value) in &new_entries {
+        new_values.entry(key.as_str()).or_insert(*value);
+    }
+    let mut steps = Vec::new();
+    let mut seen = HashSet::new();
+    for (key, old_value) in &old_entries {
+        if !seen.insert(key.as_str()) {
+            continue;
+        }
+        let key_path = child_path(path, PathSegment::Key(key.clone()));
+        match new_values.get(key.as_str()) {
+            Some(&new_value) => {
+                steps.push(Step::Compare(key_path, *old_value, new_value))
+            }
+            None => steps.push(Step::Report(DiffOp::Removed(key_path))),
+        }
+    }
+    for (key, _) in &new_entries {
+        if seen.insert(key.as_str()) {
+            let key_path = child_path(path, PathSegment::Key(key.clone()));
+            steps.push(Step::Report(DiffOp::Added(key_path)));
+        }
+    }
+    Ok(steps)
+}
+
+fn diff_arrays<'a, 'b>(
+    path: &[PathSegment],
+    old: RawElement<'a>,
+    new: RawElement<'b>,
+) -> Result<Vec<Step<'a, 'b>>> {
+    let mut old_children = old.children();
+    let mut new_children = new.children();
+    let mut steps = Vec::new();
+    for index in 0.. {
+        let index_path = || child_path(path, PathSegment::Index(index));
+        let step = match (
+            old_children.next().transpose()?,
+            new_children.next().transpose()?,
+        ) {
+            (Some(o), Some(n)) => Step::Compare(index_path(), o, n),
+            (Some(_), None) => Step::Report(DiffOp::Removed(index_path())),
+            (None, Some(_)) => Step::Report(DiffOp::Added(index_path())),
+            (None, None) => break,
+        };
+        steps.push(step);
+    }
+    Ok(steps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(k: &str) -> PathSegment {
+        PathSegment::Key(k.into())
+    }
+
+    #[test]
+    fn test_diff_one_key() {
+        // {"a": 1, "b": [true]} and {"a": 2, "b": [true]}
+        let old = b"\x8c\x17a\x131\x17b\x1b\x01";
+        let new = b"\x8c\x17a\x132\x17b\x1b\x01";
+        assert_eq!(diff(old, new).unwrap(), [DiffOp::Changed(vec![key("a")])]);
+        assert_eq!(diff(old, old).unwrap(), []);
+    }
+
+    #[test]
+    fn test_diff_added_and_removed() {
+        // {"a": 1, "b": [1, 2]}
+        let old = b"\xbc\x17a\x131\x17b\x4b\x131\x132";
+        // {"b": [1, 3, 4], "c": null}
+        let new = b"\xcc\x0c\x17b\x6b\x131\x133\x134\x17c\x00";
+        assert_eq!(
+            diff(old, new).unwrap(),
+            [
+                DiffOp::Removed(vec![key("a")]),
+                DiffOp::Changed(vec![key("b"), PathSegment::Index(1)]),
+                DiffOp::Added(vec![key("b"), PathSegment::Index(2)]),
+                DiffOp::Added(vec![key("c")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_scalars() {
+        // the same string, stored as Text and as TextJ
+        assert_eq!(diff(b"\x17a", b"\x68\\u0061").unwrap(), []);
+        // a non-minimal header
+        assert_eq!(diff(b"\x131", b"\xc3\x011").unwrap(), []);
+        assert_eq!(diff(b"\x131", b"\x0b").unwrap(), [DiffOp::Changed(vec![])]);
+    }
+
+    #[test]
+    fn test_semantic_eq_integer_encodings() {
+        let one: [&[u8]; 5] = [
+            b"\x131",
+            b"\xc3\x011",
+            b"\xd3\x00\x011",
+            b"\xe3\x00\x00\x00\x011",
+            b"\xf3\x00\x00\x00\x00\x00\x00\x00\x011",
+        ];
+        for a in one {
+            for b in one {
+                assert!(semantic_eq(a, b).unwrap());
+            }
+        }
+        #[cfg(feature = "serde_json5")]
+        assert!(semantic_eq(b"\x131", b"\x340x1").unwrap());
+        assert!(!semantic_eq(b"\x131", b"\x132").unwrap());
+        // an integer is not equal to a float
+        assert!(!semantic_eq(b"\x131", b"\x351.0").unwrap());
+        assert!(semantic_eq(b"\x351.0", b"\x5510e-1").unwrap());
+    }
+
+    #[test]
+    fn test_semantic_eq_nested() {
+        // {"a": "x", "b": [1]}
+        let a = b"\x9c\x17a\x17x\x17b\x2b\x131";
+        // {"b": [1], "a": "x"}, with TextJ and TextRaw text and a
+        // non-minimal array header
+        let b = b"\xcc\x0a\x1ab\xcb\x02\x131\x1aa\x18x";
+        assert!(semantic_eq(a, b).unwrap());
+        // {"a": "y", "b": [1]}
+        let c = b"\x9c\x17a\x17y\x17b\x2b\x131";
+        assert!(!semantic_eq(a, c).unwrap());
+        assert!(semantic_eq(a, b"\x131\x00").is_err());
+    }
+}
@@ -0,0 +1,130 @@
+//! (De)serialization of [`Duration`]s stored either as a number of seconds or
+//! as human readable text, for use with `#[serde(with = "...")]`.
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! #[derive(serde::Deserialize)]
+//! struct Job {
+//!     #[serde(with = "serde_sqlite_jsonb::duration")]
+//!     timeout: Duration,
+//! }
+//! ```
+//!
+//! Text durations are parsed by [`humantime::parse_duration`]: they are
+//! sequences of numbers followed by a unit, like `"1.5s"` or `"1h 30min"`.
+//! This module requires the `humantime` feature.
+
+use serde::de::{self, Deserializer, Visitor};
+use serde::ser::Serializer;
+use std::fmt;
+use std::time::Duration;
+
+/// Serialize a duration as an integer number of seconds when it is one, and
+/// as text formatted by [`humantime::format_duration`] otherwise.
+pub fn serialize<S: Serializer>(
+    duration: &Duration,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let (secs, nanos) = (duration.as_secs(), duration.subsec_nanos());
+    if nanos == 0 {
+        serializer.serialize_u64(secs)
+    } else {
+        serializer.collect_str(&humantime::format_duration(*duration))
+    }
+}
+
+/// Deserialize a duration from a number of seconds, or from text.
+pub fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Duration, D::Error> {
+    deserializer.deserialize_any(DurationVisitor)
+}
+
+struct DurationVisitor;
+
+impl<'de> Visitor<'de> for DurationVisitor {
+    type Value = Duration;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter
+            .write_str("a number of seconds or a duration like \"1h 30min\"")
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Duration, E> {
+        Ok(Duration::from_secs(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Duration, E> {
+        u64::try_from(v)
+            .map(Duration::from_secs)
+            .map_err(|_| E::custom("a duration cannot be negative"))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Duration, E> {
+        if v.is_finite() && v >= 0. && v < u64::MAX as f64 {
+            Ok(Duration::from_secs_f64(v))
+        } else {
+            Err(E::custom(format!("invalid number of seconds: {v}")))
+        }
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Duration, E> {
+        humantime::parse_duration(v)
+            .map_err(|e| E::custom(format!("invalid duration {v:?}: {e}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(
+        Debug, PartialEq, serde_derive::Serialize, serde_derive::Deserialize,
+    )]
+    struct Job {
+        #[serde(with = "super")]
+        timeout: Duration,
+    }
+
+    fn decode(timeout: &[u8]) -> crate::Result<Duration> {
+        // {"timeout": <timeout>}
+        let mut bytes = vec![0xcc, (8 + timeout.len()) as u8];
+        bytes.extend_from_slice(b"\x77timeout");
+        bytes.extend_from_slice(timeout);
+        crate::from_slice::<Job>(&bytes).map(|job| job.timeout)
+    }
+
+    #[test]
+    fn test_duration_from_int() {
+        assert_eq!(decode(b"\x2390"), Ok(Duration::from_secs(90)));
+        assert!(decode(b"\x23-1").is_err());
+    }
+
+    #[test]
+    fn test_duration_from_text() {
+        assert_eq!(decode(b"\x771s500ms"), Ok(Duration::from_millis(1500)));
+        assert_eq!(decode(b"\x871h 30min"), Ok(Duration::from_secs(5400)));
+        assert_eq!(decode(b"\x57250ms"), Ok(Duration::from_millis(250)));
+        assert_eq!(decode(b"\x972days 1ns"), Ok(Duration::new(2 * 86_400, 1)));
+        assert_eq!(decode(b"\x272w"), Ok(Duration::from_secs(14 * 86_400)));
+        assert_eq!(decode(b"\x471.5s"), Ok(Duration::from_millis(1500)));
+        assert_eq!(decode(b"\x572 min"), Ok(Duration::from_secs(120)));
+        assert!(decode(b"\x375xs").is_err());
+        assert!(decode(b"\x17s").is_err());
+        assert!(decode(b"\x07").is_err());
+    }
+
+    #[test]
+    fn test_duration_roundtrip() {
+        for timeout in [
+            Duration::from_secs(3),
+            Duration::from_millis(1500),
+            Duration::new(1, 1),
+        ] {
+            let job = Job { timeout };
+            let encoded = crate::to_vec(&job).unwrap();
+            assert_eq!(crate::from_slice::<Job>(&encoded).unwrap(), job);
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use serde::{de, ser};
 use std::fmt::{self, Display};
 
-use crate::header::ElementType;
+use crate::header::{ElementType, HeaderWidth};
 
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -12,9 +12,87 @@ pub enum Error {
     JsonError(crate::json::JsonError),
     Json5Error(crate::json::Json5Error),
     InvalidElementType(u8),
-    UnexpectedType(ElementType),
+    /// The element does not have a type that can be decoded into the
+    /// requested value.
+    UnexpectedType {
+        /// Description of the element types that were expected.
+        expected: &'static str,
+        /// Type of the element that was found.
+        found: ElementType,
+    },
+    /// The element uses one of the element types that the JSONB format
+    /// reserves for future use.
+    ReservedElementType(ElementType),
+    /// The input ended in the middle of an element.
+    UnexpectedEof,
     Io(std::io::Error),
-    TrailingCharacters,
+    /// There is data left after the end of the decoded value.
+    TrailingCharacters {
+        /// Number of bytes left, from the start of the trailing data. When
+        /// the length of the input is not known in advance, as with
+        /// [`from_reader`](crate::from_reader), the rest of the input is not
+        /// read, and only the bytes read past the value are counted.
+        remaining: usize,
+        /// Type of the element that the trailing data starts with.
+        next_element_type: ElementType,
+    },
+    /// The value contains more than the given number of elements.
+    TooManyElements(usize),
+    /// The input is larger than the given number of bytes.
+    InputTooLarge(usize),
+    /// The value contains an element of one of the types that only exist
+    /// in JSON5: `Int5`, `Float5` or `Text5`.
+    Json5ElementPresent(ElementType),
+    /// The text elements of the value are larger, in total, than the given
+    /// number of bytes.
+    StringBudgetExceeded(usize),
+    /// Arrays and objects are nested deeper than the given limit.
+    DepthLimitExceeded(usize),
+    /// A `Null`, `True` or `False` element has a payload, although these
+    /// types never carry one.
+    UnexpectedPayload(ElementType),
+    /// The payload of an element is larger than what is left of the input.
+    PayloadExceedsInput {
+        /// Size of the payload in the header
+        declared: usize,
+        /// Number of bytes left after the header
+        available: usize,
+    },
+    /// An element inside an array or object extends past the end of it.
+    ElementExceedsContainer,
+    /// A header of the requested width cannot hold the size of the payload.
+    HeaderTooNarrow {
+        width: HeaderWidth,
+        payload_size: usize,
+    },
+    /// The size field of a header was cut short.
+    TruncatedHeader {
+        /// Number of bytes the size field should span.
+        expected: usize,
+        /// Number of bytes that were actually available.
+        got: usize,
+    },
+    /// The payload of an `Int` element is not an integer in the canonical
+    /// RFC 8259 format, for instance because of whitespace or leading zeros.
+    NonCanonicalInteger(String),
+    /// The payload of an `Int` element is a number with a fractional part.
+    IntegerHasFraction(String),
+    /// An integer does not fit in the type it is deserialized into.
+    NumberOutOfRange {
+        /// The integer, in decimal
+        value: String,
+        /// The name of the integer type
+        target: &'static str,
+    },
+    /// A float is NaN or infinite, which is rejected unless
+    /// [`with_allow_non_finite`](crate::Deserializer::with_allow_non_finite)
+    /// is enabled.
+    NonFiniteFloat,
+    /// An object ends after a key, without a value for it.
+    MissingObjectValue,
+    /// Decoding was aborted with the flag set by
+    /// [`with_cancel_flag`](crate::Deserializer::with_cancel_flag).
+    Cancelled,
     Utf8(std::string::FromUtf8Error),
     Empty,
 }
@@ -40,17 +118,197 @@ impl Display for Error {
             Error::InvalidElementType(t) => {
                 write!(f, "{t} is not a valid jsonb element type code")
             }
-            Error::UnexpectedType(t) => write!(f, "unexpected type: {t:?}"),
+            Error::UnexpectedType { expected, found } => {
+                write!(
+                    f,
+                    "unexpected type: expected {expected}, found {found:?}"
+                )
+            }
+            Error::ReservedElementType(t) => {
+                write!(f, "{t:?} is a reserved element type")
+            }
+            Error::UnexpectedEof => write!(f, "unexpected end of jsonb data"),
             Error::Io(_) => write!(f, "io error"),
-            Error::TrailingCharacters => {
-                write!(f, "trailing data after the end of the jsonb value")
+            Error::TrailingCharacters {
+                remaining,
+                next_element_type,
+            } => write!(
+                f,
+                "{remaining} trailing bytes after the end of the jsonb value, \
+                starting with an element of type {next_element_type:?}"
+            ),
+            Error::TooManyElements(max) => {
+                write!(f, "the jsonb value contains more than {max} elements")
+            }
+            Error::InputTooLarge(max) => {
+                write!(f, "the jsonb input is larger than {max} bytes")
+            }
+            Error::Json5ElementPresent(t) => {
+                write!(f, "the jsonb value contains a JSON5 element: {t:?}")
+            }
+            Error::StringBudgetExceeded(max) => write!(
+                f,
+                "the strings of the jsonb value are larger than {max} bytes in total"
+            ),
+            Error::DepthLimitExceeded(max) => write!(
+                f,
+                "arrays and objects are nested more than {max} levels deep"
+            ),
+            Error::UnexpectedPayload(t) => {
+                write!(f, "unexpected payload in a {t:?} element")
+            }
+            Error::PayloadExceedsInput {
+                declared,
+                available,
+            } => write!(
+                f,
+                "an element declares a payload of {declared} bytes, \
+                but only {available} bytes are left in the input"
+            ),
+            Error::ElementExceedsContainer => {
+                write!(f, "an element extends past the end of its container")
             }
+            Error::HeaderTooNarrow {
+                width,
+                payload_size,
+            } => write!(
+                f,
+                "a payload of {payload_size} bytes does not fit in a header of width {width:?}"
+            ),
+            Error::TruncatedHeader { expected, got } => write!(
+                f,
+                "truncated header: expected {expected} size bytes, got {got}"
+            ),
+            Error::NonCanonicalInteger(payload) => {
+                write!(f, "{payload:?} is not a canonical integer")
+            }
+            Error::IntegerHasFraction(payload) => write!(
+                f,
+                "the payload of an integer element, {payload:?}, has a fractional part"
+            ),
+            Error::NumberOutOfRange { value, target } => {
+                write!(f, "{value} is out of the range of {target}")
+            }
+            Error::NonFiniteFloat => {
+                write!(f, "a float is NaN or infinite")
+            }
+            Error::MissingObjectValue => {
+                write!(f, "an object ends with a key that has no value")
+            }
+            Error::Cancelled => write!(f, "decoding was cancelled"),
             Error::Utf8(_) => write!(f, "invalid utf8 in string"),
             Error::Empty => write!(f, "empty jsonb value"),
         }
     }
 }
 
+impl PartialEq for Error {
+    /// Errors are equal when they are of the same kind and carry the same
+    /// data. Wrapped parser errors are compared by their message, and I/O
+    /// errors by their [`std::io::ErrorKind`] only.
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Error::Message(a), Error::Message(b)) => a == b,
+            (Error::JsonError(a), Error::JsonError(b)) => {
+                a.to_string() == b.to_string()
+            }
+            (Error::Json5Error(a), Error::Json5Error(b)) => {
+                a.to_string() == b.to_string()
+            }
+            (Error::InvalidElementType(a), Error::InvalidElementType(b)) => {
+                a == b
+            }
+            (
+                Error::UnexpectedType { expected, found },
+                Error::UnexpectedType {
+                    expected: e,
+                    found: f,
+                },
+            ) => expected == e && found == f,
+            (Error::ReservedElementType(a), Error::ReservedElementType(b)) => {
+                a == b
+            }
+            (Error::UnexpectedEof, Error::UnexpectedEof) => true,
+            (Error::Io(a), Error::Io(b)) => a.kind() == b.kind(),
+            (
+                Error::TrailingCharacters {
+                    remaining,
+                    next_element_type,
+                },
+                Error::TrailingCharacters {
+                    remaining: r,
+                    next_element_type: n,
+                },
+            ) => remaining == r && next_element_type == n,
+            (Error::TooManyElements(a), Error::TooManyElements(b)) => a == b,
+            (Error::InputTooLarge(a), Error::InputTooLarge(b)) => a == b,
+            (Error::Json5ElementPresent(a), Error::Json5ElementPresent(b)) => {
+                a == b
+            }
+            (
+                Error::StringBudgetExceeded(a),
+                Error::StringBudgetExceeded(b),
+            ) => a == b,
+            (Error::DepthLimitExceeded(a), Error::DepthLimitExceeded(b)) => {
+                a == b
+            }
+            (Error::UnexpectedPayload(a), Error::UnexpectedPayload(b)) => {
+                a == b
+            }
+            (
+                Error::PayloadExceedsInput {
+                    declared,
+                    available,
+                },
+                Error::PayloadExceedsInput {
+                    declared: d,
+                    available: a,
+                },
+            ) => declared == d && available == a,
+            (
+                Error::ElementExceedsContainer,
+                Error::ElementExceedsContainer,
+            ) => true,
+            (
+                Error::HeaderTooNarrow {
+                    width,
+                    payload_size,
+                },
+                Error::HeaderTooNarrow {
+                    width: w,
+                    payload_size: p,
+                },
+            ) => width == w && payload_size == p,
+            (
+                Error::TruncatedHeader { expected, got },
+                Error::TruncatedHeader {
+                    expected: e,
+                    got: g,
+                },
+            ) => expected == e && got == g,
+            (Error::NonCanonicalInteger(a), Error::NonCanonicalInteger(b)) => {
+                a == b
+            }
+            (Error::IntegerHasFraction(a), Error::IntegerHasFraction(b)) => {
+                a == b
+            }
+            (
+                Error::NumberOutOfRange { value, target },
+                Error::NumberOutOfRange {
+                    value: v,
+                    target: t,
+                },
+            ) => value == v && target == t,
+            (Error::NonFiniteFloat, Error::NonFiniteFloat) => true,
+            (Error::MissingObjectValue, Error::MissingObjectValue) => true,
+            (Error::Cancelled, Error::Cancelled) => true,
+            (Error::Utf8(a), Error::Utf8(b)) => a == b,
+            (Error::Empty, Error::Empty) => true,
+            _ => false,
+        }
+    }
+}
+
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
@@ -65,7 +323,13 @@ impl std::error::Error for Error {
 
 impl From<std::io::Error> for Error {
     fn from(err: std::io::Error) -> Error {
-        Error::Io(err)
+        if err.kind() == std::io::ErrorKind::UnexpectedEof {
+            Error::UnexpectedEof
+        } else if let Some(max) = crate::limited_reader::limit_exceeded(&err) {
+            Error::InputTooLarge(max)
+        } else {
+            Error::Io(err)
+        }
     }
 }
 
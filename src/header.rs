@@ -1,4 +1,6 @@
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize,
+)]
 #[repr(u8)]
 /// Represents the different element types in the JSONB format.
 pub enum ElementType {
@@ -36,6 +38,63 @@ pub enum ElementType {
     Reserved15 = 0xF,
 }
 
+/// The forms a header can take, one per size of the payload size field.
+///
+/// The format allows any form that can hold the payload size, not only the
+/// smallest one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum HeaderWidth {
+    /// A single byte holding the payload size, up to 11
+    Inline,
+    /// A one-byte payload size after the first byte
+    U8,
+    /// A two-byte payload size after the first byte
+    U16,
+    /// A four-byte payload size after the first byte
+    U32,
+    /// An eight-byte payload size after the first byte
+    U64,
+}
+
+impl HeaderWidth {
+    /// The smallest width that can hold the given payload size.
+    pub fn minimal(payload_size: usize) -> Self {
+        [
+            HeaderWidth::Inline,
+            HeaderWidth::U8,
+            HeaderWidth::U16,
+            HeaderWidth::U32,
+        ]
+        .iter()
+        .copied()
+        .find(|width| width.can_hold(payload_size))
+        .unwrap_or(HeaderWidth::U64)
+    }
+
+    /// Number of bytes of a header of this width.
+    pub fn header_len(self) -> usize {
+        match self {
+            HeaderWidth::Inline => 1,
+            HeaderWidth::U8 => 2,
+            HeaderWidth::U16 => 3,
+            HeaderWidth::U32 => 5,
+            HeaderWidth::U64 => 9,
+        }
+    }
+
+    /// Whether a header of this width can hold the given payload size.
+    pub fn can_hold(self, payload_size: usize) -> bool {
+        let max = match self {
+            HeaderWidth::Inline => 11,
+            HeaderWidth::U8 => 0xff,
+            HeaderWidth::U16 => 0xffff,
+            HeaderWidth::U32 => 0xffff_ffff,
+            HeaderWidth::U64 => u64::MAX,
+        };
+        payload_size as u64 <= max
+    }
+}
+
 /// Represents the header of a JSONB element (size and type).
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Header {
@@ -44,20 +103,133 @@ pub struct Header {
 }
 
 impl Header {
-    /// Serialize the header into a byte array.
-    pub fn serialize(self) -> [u8; 9] {
-        let mut s = [0u8; 9];
-        s[0] = u8::from(self.element_type) | 0xF0;
-        let payload_size = self.payload_size.to_be_bytes();
-        s[1..].copy_from_slice(&payload_size);
-        s
+    /// Number of bytes following the first byte of a header that hold the
+    /// payload size.
+    pub(crate) fn size_field_len(first_byte: u8) -> usize {
+        /*
+         If the upper four bits have a value between 0 and 11,
+        then the header is exactly one byte in size and the payload size is determined by those upper four bits.
+
+        If the upper four bits have a value between 12 and 15,
+        that means that the total header size is 2, 3, 5, or 9 bytes and the payload size is unsigned big-endian integer that is contained in the subsequent bytes.
+
+        The size integer is
+          - the one byte that following the initial header byte if the upper four bits are 12,
+          - two bytes if the upper bits are 13,
+          - four bytes if the upper bits are 14,
+          - and eight bytes if the upper bits are 15.
+        */
+        match first_byte >> 4 {
+            0..=11 => 0,
+            12 => 1,
+            13 => 2,
+            14 => 4,
+            15 => 8,
+            n => unreachable!("{n} does not fit in four bits"),
+        }
+    }
+
+    /// Build a header from its first byte and its (possibly empty)
+    /// big-endian size field.
+    pub(crate) fn from_parts(first_byte: u8, size_field: &[u8]) -> Header {
+        let payload_size = if size_field.is_empty() {
+            usize::from(first_byte >> 4)
+        } else {
+            let mut buf = [0u8; 8];
+            buf[8 - size_field.len()..].copy_from_slice(size_field);
+            usize::from_be_bytes(buf)
+        };
+        Header {
+            element_type: ElementType::from_header_byte(first_byte),
+            payload_size,
+        }
+    }
+
+    /// Encode the header in as few bytes as possible.
+    /// Returns a buffer along with the number of bytes of it that are used.
+    pub(crate) fn to_minimal_bytes(self) -> ([u8; 9], usize) {
+        self.to_bytes_with_width(HeaderWidth::minimal(self.payload_size))
+            .expect("the minimal width holds the payload size")
+    }
+
+    /// Encode the header with the given width, which must be able to hold
+    /// its payload size. Returns a buffer along with the number of bytes of
+    /// it that are used.
+    pub(crate) fn to_bytes_with_width(
+        self,
+        width: HeaderWidth,
+    ) -> crate::Result<([u8; 9], usize)> {
+        let size = self.payload_size;
+        if !width.can_hold(size) {
+            return Err(crate::Error::HeaderTooNarrow {
+                width,
+                payload_size: size,
+            });
+        }
+        let mut bytes = [u8::from(self.element_type); 9];
+        match width {
+            HeaderWidth::Inline => bytes[0] |= (size as u8) << 4,
+            HeaderWidth::U8 => {
+                bytes[0] |= 0xc0;
+                bytes[1] = size as u8;
+            }
+            HeaderWidth::U16 => {
+                bytes[0] |= 0xd0;
+                bytes[1..3].copy_from_slice(&(size as u16).to_be_bytes());
+            }
+            HeaderWidth::U32 => {
+                bytes[0] |= 0xe0;
+                bytes[1..5].copy_from_slice(&(size as u32).to_be_bytes());
+            }
+            HeaderWidth::U64 => {
+                bytes[0] |= 0xf0;
+                bytes[1..9].copy_from_slice(&(size as u64).to_be_bytes());
+            }
+        }
+        Ok((bytes, width.header_len()))
     }
 }
 
-impl std::convert::From<u8> for ElementType {
-    fn from(value: u8) -> Self {
-        match value & 0x0F {
-            // Element types are stored in the lower 4 bits
+impl ElementType {
+    /// Whether the type is one of the four string types.
+    pub(crate) fn is_text(self) -> bool {
+        matches!(
+            self,
+            ElementType::Text
+                | ElementType::TextJ
+                | ElementType::Text5
+                | ElementType::TextRaw
+        )
+    }
+
+    /// Whether the type is one of those reserved for future expansion.
+    pub(crate) fn is_reserved(self) -> bool {
+        matches!(
+            self,
+            ElementType::Reserved13
+                | ElementType::Reserved14
+                | ElementType::Reserved15
+        )
+    }
+
+    /// The type of the element whose header starts with `first_byte`.
+    /// Element types are stored in its lower four bits.
+    pub(crate) fn from_header_byte(first_byte: u8) -> Self {
+        match ElementType::try_from(first_byte & 0x0F) {
+            Ok(element_type) => element_type,
+            Err(_) => {
+                unreachable!("A four-bit number cannot be larger than 15")
+            }
+        }
+    }
+}
+
+/// Convert a type tag, between 0 and 15, to the corresponding element type.
+impl std::convert::TryFrom<u8> for ElementType {
+    type Error = crate::Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
             0 => ElementType::Null,
             1 => ElementType::True,
             2 => ElementType::False,
@@ -74,13 +246,56 @@ impl std::convert::From<u8> for ElementType {
             0xD => ElementType::Reserved13,
             0xE => ElementType::Reserved14,
             0xF => ElementType::Reserved15,
-            _ => unreachable!("A four-bit number cannot be larger than 15"),
-        }
+            _ => return Err(crate::Error::InvalidElementType(value)),
+        })
     }
 }
 
+/// The type tag of an element type, between 0 and 15.
 impl std::convert::From<ElementType> for u8 {
     fn from(value: ElementType) -> Self {
         value as u8
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_element_type_serde() {
+        let encoded = crate::to_vec(&ElementType::TextRaw).unwrap();
+        assert_eq!(encoded, b"\x77TextRaw");
+        assert_eq!(
+            crate::from_slice::<ElementType>(&encoded).unwrap(),
+            ElementType::TextRaw
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde_json")]
+    fn test_element_type_to_json() {
+        assert_eq!(
+            serde_json::to_string(&ElementType::TextRaw).unwrap(),
+            "\"TextRaw\""
+        );
+    }
+
+    #[test]
+    fn test_element_type_tag_roundtrip() {
+        for tag in 0..=15u8 {
+            let element_type = ElementType::try_from(tag).unwrap();
+            assert_eq!(u8::from(element_type), tag);
+        }
+        assert_eq!(
+            ElementType::try_from(16),
+            Err(crate::Error::InvalidElementType(16))
+        );
+    }
+
+    #[test]
+    fn test_element_type_from_header_byte() {
+        assert_eq!(ElementType::from_header_byte(0x13), ElementType::Int);
+        assert_eq!(ElementType::from_header_byte(0xfc), ElementType::Object);
+    }
+}
@@ -0,0 +1,176 @@
+//! The smallest Rust integer types that can hold the integers of JSONB data.
+
+use crate::error::{Error, Result};
+use crate::header::ElementType;
+
+/// A Rust integer type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum IntWidth {
+    /// `u8`
+    U8,
+    /// `i8`
+    I8,
+    /// `u16`
+    U16,
+    /// `i16`
+    I16,
+    /// `u32`
+    U32,
+    /// `i32`
+    I32,
+    /// `u64`
+    U64,
+    /// `i64`
+    I64,
+    /// `u128`
+    U128,
+    /// `i128`
+    I128,
+}
+
+impl IntWidth {
+    fn of_unsigned(u: u128) -> Self {
+        if u <= u128::from(u8::MAX) {
+            IntWidth::U8
+        } else if u <= u128::from(u16::MAX) {
+            IntWidth::U16
+        } else if u <= u128::from(u32::MAX) {
+            IntWidth::U32
+        } else if u <= u128::from(u64::MAX) {
+            IntWidth::U64
+        } else {
+            IntWidth::U128
+        }
+    }
+
+    /// The smallest type that can hold every value of both types, or
+    /// `I128` if there is none.
+    pub(crate) fn union(self, other: IntWidth) -> Self {
+        let (min_a, max_a) = self.bounds();
+        let (min_b, max_b) = other.bounds();
+        let (min, max) = (min_a.min(min_b), max_a.max(max_b));
+        if min >= 0 {
+            return IntWidth::of_unsigned(max);
+        }
+        [IntWidth::I8, IntWidth::I16, IntWidth::I32, IntWidth::I64]
+            .iter()
+            .copied()
+            .find(|width| {
+                let (width_min, width_max) = width.bounds();
+                width_min <= min && width_max >= max
+            })
+            .unwrap_or(IntWidth::I128)
+    }
+
+    /// The smallest and largest values of the type.
+    fn bounds(self) -> (i128, u128) {
+        fn signed(min: i128, max: i128) -> (i128, u128) {
+            (min, max as u128)
+        }
+        match self {
+            IntWidth::U8 => (0, u8::MAX.into()),
+            IntWidth::I8 => signed(i8::MIN.into(), i8::MAX.into()),
+            IntWidth::U16 => (0, u16::MAX.into()),
+            IntWidth::I16 => signed(i16::MIN.into(), i16::MAX.into()),
+            IntWidth::U32 => (0, u32::MAX.into()),
+            IntWidth::I32 => signed(i32::MIN.into(), i32::MAX.into()),
+            IntWidth::U64 => (0, u64::MAX.into()),
+            IntWidth::I64 => signed(i64::MIN.into(), i64::MAX.into()),
+            IntWidth::U128 => (0, u128::MAX),
+            IntWidth::I128 => signed(i128::MIN, i128::MAX),
+        }
+    }
+
+    fn of_negative(i: i128) -> Self {
+        if i >= i128::from(i8::MIN) {
+            IntWidth::I8
+        } else if i >= i128::from(i16::MIN) {
+            IntWidth::I16
+        } else if i >= i128::from(i32::MIN) {
+            IntWidth::I32
+        } else if i >= i128::from(i64::MIN) {
+            IntWidth::I64
+        } else {
+            IntWidth::I128
+        }
+    }
+}
+
+/// Return the smallest integer type that can hold the integer at the root of
+/// a JSONB value: an unsigned type for values that are not negative, and a
+/// signed one otherwise.
+///
+/// The root must be an `Int` or `Int5` element.
+pub fn min_int_type(bytes: &[u8]) -> Result<IntWidth> {
+    match crate::root_type(bytes)? {
+        ElementType::Int | ElementType::Int5 => {}
+        t => {
+            return Err(Error::UnexpectedType {
+                expected: "Int or Int5",
+                found: t,
+            })
+        }
+    }
+    match crate::from_slice::<i128>(bytes) {
+        Ok(i) if i < 0 => Ok(IntWidth::of_negative(i)),
+        Ok(i) => Ok(IntWidth::of_unsigned(i as u128)),
+        // too large for an i128
+        Err(e) => crate::from_slice::<u128>(bytes)
+            .map(IntWidth::of_unsigned)
+            .map_err(|_| e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn width(n: impl std::fmt::Display) -> IntWidth {
+        let text = n.to_string();
+        let mut bytes = vec![0xc3, text.len() as u8];
+        bytes.extend_from_slice(text.as_bytes());
+        min_int_type(&bytes).unwrap()
+    }
+
+    #[test]
+    fn test_min_int_type() {
+        assert_eq!(width(0), IntWidth::U8);
+        assert_eq!(width(255), IntWidth::U8);
+        assert_eq!(width(256), IntWidth::U16);
+        assert_eq!(width(-1), IntWidth::I8);
+        assert_eq!(width(-128), IntWidth::I8);
+        assert_eq!(width(-129), IntWidth::I16);
+        assert_eq!(width(u32::MAX), IntWidth::U32);
+        assert_eq!(width(i32::MIN), IntWidth::I32);
+        assert_eq!(width(i64::MIN), IntWidth::I64);
+    }
+
+    #[test]
+    fn test_min_int_type_large() {
+        assert_eq!(width(u64::MAX), IntWidth::U64);
+        assert_eq!(width(i128::from(i64::MIN) - 1), IntWidth::I128);
+        assert_eq!(width(u128::from(u64::MAX) + 1), IntWidth::U128);
+        assert_eq!(width(u128::MAX), IntWidth::U128);
+    }
+
+    #[test]
+    fn test_min_int_type_not_an_integer() {
+        assert_eq!(
+            min_int_type(b"\x351.5"),
+            Err(Error::UnexpectedType {
+                expected: "Int or Int5",
+                found: ElementType::Float,
+            })
+        );
+    }
+
+    #[test]
+    fn test_union() {
+        assert_eq!(IntWidth::U8.union(IntWidth::U16), IntWidth::U16);
+        assert_eq!(IntWidth::I8.union(IntWidth::I8), IntWidth::I8);
+        assert_eq!(IntWidth::U8.union(IntWidth::I8), IntWidth::I16);
+        assert_eq!(IntWidth::I32.union(IntWidth::U16), IntWidth::I32);
+        assert_eq!(IntWidth::U64.union(IntWidth::I8), IntWidth::I128);
+        assert_eq!(IntWidth::U128.union(IntWidth::I8), IntWidth::I128);
+    }
+}
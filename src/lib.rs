@@ -1,9 +1,63 @@
+#[cfg(feature = "base64")]
+mod base64;
+mod bytes;
+mod canonical;
+mod config;
 mod de;
+#[cfg(feature = "tokio")]
+mod de_async;
+#[cfg(feature = "rust_decimal")]
+pub mod decimal;
+mod diff;
+#[cfg(feature = "humantime")]
+pub mod duration;
 mod error;
 mod header;
+mod int_width;
 mod json;
+mod limited_reader;
+mod normalize;
+pub mod number_text;
+mod printer;
+mod raw;
+mod raw_value;
+mod schema;
 mod ser;
+#[cfg(feature = "bytes")]
+pub mod shared_text;
+mod summary;
+mod validate;
+mod value;
 
-pub use crate::de::{from_reader, from_slice, Deserializer};
+#[cfg(feature = "base64")]
+pub use crate::base64::Base64;
+pub use crate::bytes::BytesEncoding;
+pub use crate::canonical::{
+    canonical_len, canonicalize, canonicalize_to, reencode_with_width,
+};
+pub use crate::config::DeserializerConfig;
+#[cfg(feature = "bytes")]
+pub use crate::de::from_bytes_buf;
+pub use crate::de::{
+    from_bytes_typed, from_bytes_with_config, from_reader, from_reader_partial,
+    from_slice, from_vec, root_type, Deserializer, NumberHook, Observer,
+    OnReserved, ParsedNumber,
+};
+#[cfg(feature = "tokio")]
+pub use crate::de_async::from_async_reader;
+pub use crate::diff::{diff, semantic_eq, DiffOp, PathSegment};
 pub use crate::error::{Error, Result};
+pub use crate::header::{ElementType, Header, HeaderWidth};
+pub use crate::int_width::{min_int_type, IntWidth};
+pub use crate::normalize::{normalize_text, optimize};
+pub use crate::printer::{to_json_string, to_json_string_pretty, PrettyConfig};
+pub use crate::raw::{
+    assert_strict_json, get_field, iter_array_raw, object_keys, object_values,
+    pairs_to_map,
+};
+pub use crate::raw_value::JsonbRawValue;
+pub use crate::schema::{infer_schema, Schema};
 pub use crate::ser::{to_vec, Serializer};
+pub use crate::summary::{element_type_histogram, summarize, Summary};
+pub use crate::validate::{validate_all, validate_all_with};
+pub use crate::value::{JsonbValue, NumberSubtype, TextSubtype};
@@ -0,0 +1,196 @@
+//! A reader that counts the bytes read through it, and stops at a limit.
+
+use crate::error::{Error, Result};
+use std::io::{self, Read};
+
+/// Wraps the reader of a [`Deserializer`](crate::Deserializer), to enforce
+/// [`with_max_input_bytes`](crate::Deserializer::with_max_input_bytes) in a
+/// single place.
+///
+/// Reading past the limit fails with an I/O error that converts to
+/// [`Error::InputTooLarge`].
+#[derive(Debug)]
+pub(crate) struct LimitedCountingReader<R> {
+    inner: R,
+    /// Number of bytes read so far.
+    count: usize,
+    /// Maximum number of bytes that may be read.
+    limit: Option<usize>,
+    /// Total length of the input, when it is known in advance.
+    input_len: Option<usize>,
+    /// Moves the inner reader forward by a number of bytes, when it can
+    /// seek.
+    seek_forward: Option<fn(&mut R, u64) -> io::Result<()>>,
+}
+
+/// The payload of the I/O error returned when the limit is exceeded.
+#[derive(Debug)]
+struct LimitExceeded(usize);
+
+impl std::fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "the input is larger than {} bytes", self.0)
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+impl<R> LimitedCountingReader<R> {
+    pub(crate) fn new(inner: R) -> Self {
+        LimitedCountingReader {
+            inner,
+            count: 0,
+            limit: None,
+            input_len: None,
+            seek_forward: None,
+        }
+    }
+
+    pub(crate) fn set_limit(&mut self, limit: usize) {
+        self.limit = Some(limit);
+    }
+
+    /// The maximum number of bytes that may be read, if there is one.
+    pub(crate) fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    /// Record the total length of the input, for [`Self::available`].
+    pub(crate) fn set_input_len(&mut self, len: usize) {
+        self.input_len = Some(len);
+    }
+
+    /// Number of bytes left in the input, if its length is known.
+    pub(crate) fn input_left(&self) -> Option<usize> {
+        self.input_len.map(|len| len.saturating_sub(self.count))
+    }
+
+    /// Number of bytes that can still be read: those left in the input, if
+    /// its length is known, and at most those left before the limit.
+    pub(crate) fn available(&self) -> Option<usize> {
+        let before_limit = self.limit.map(|max| max.saturating_sub(self.count));
+        match (self.input_left(), before_limit) {
+            (Some(left), Some(before_limit)) => Some(left.min(before_limit)),
+            (left, before_limit) => left.or(before_limit),
+        }
+    }
+
+    /// Make [`Self::skip`] call `seek_forward` instead of reading.
+    pub(crate) fn set_seek_forward(
+        &mut self,
+        seek_forward: fn(&mut R, u64) -> io::Result<()>,
+    ) {
+        self.seek_forward = Some(seek_forward);
+    }
+
+    /// Read from a new reader, with the count starting over.
+    pub(crate) fn reset(&mut self, inner: R) {
+        self.inner = inner;
+        self.count = 0;
+        self.input_len = None;
+    }
+
+    pub(crate) fn into_inner(self) -> R {
+        self.inner
+    }
+
+    /// Count `n` more bytes as read, failing if this exceeds the limit.
+    fn count(&mut self, n: usize) -> io::Result<()> {
+        self.count = self.count.saturating_add(n);
+        match self.limit {
+            Some(limit) if self.count > limit => {
+                Err(io::Error::new(io::ErrorKind::Other, LimitExceeded(limit)))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl<R: Read> LimitedCountingReader<R> {
+    /// Move past the next `n` bytes, and return how many there were.
+    ///
+    /// Seekable readers are moved without reading, and are trusted to hold
+    /// the bytes, which is only safe once `n` has been checked against
+    /// [`Self::available`].
+    pub(crate) fn skip(&mut self, n: usize) -> io::Result<usize> {
+        match self.seek_forward {
+            Some(seek_forward) => {
+                self.count(n)?;
+                seek_forward(&mut self.inner, n as u64)?;
+                Ok(n)
+            }
+            None => {
+                let skipped =
+                    io::copy(&mut self.take(n as u64), &mut io::sink())?;
+                // the copy is bounded by `n`
+                Ok(skipped as usize)
+            }
+        }
+    }
+}
+
+impl<'a> LimitedCountingReader<&'a [u8]> {
+    /// Split the next `n` bytes off the input, without copying them.
+    pub(crate) fn split_to(&mut self, n: usize) -> Result<&'a [u8]> {
+        if self.inner.len() < n {
+            return Err(Error::UnexpectedEof);
+        }
+        self.count(n)?;
+        let (bytes, rest) = self.inner.split_at(n);
+        self.inner = rest;
+        Ok(bytes)
+    }
+}
+
+impl<R: Read> Read for LimitedCountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count(n)?;
+        Ok(n)
+    }
+}
+
+/// Recover the limit from an error returned by a [`LimitedCountingReader`].
+pub(crate) fn limit_exceeded(err: &io::Error) -> Option<usize> {
+    err.get_ref()?
+        .downcast_ref::<LimitExceeded>()
+        .map(|LimitExceeded(limit)| *limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit_mid_read() {
+        let mut reader = LimitedCountingReader::new(&b"abcdef"[..]);
+        reader.set_limit(4);
+        let mut buf = [0u8; 3];
+        assert_eq!(reader.read(&mut buf).unwrap(), 3);
+        let err = reader.read(&mut buf).unwrap_err();
+        assert_eq!(Error::from(err), Error::InputTooLarge(4));
+    }
+
+    #[test]
+    fn test_limit_on_borrowed_split() {
+        let mut reader = LimitedCountingReader::new(&b"abcdef"[..]);
+        reader.set_limit(4);
+        assert_eq!(reader.split_to(4).unwrap(), b"abcd");
+        assert_eq!(reader.split_to(1), Err(Error::InputTooLarge(4)));
+    }
+
+    #[test]
+    fn test_skip_with_seek() {
+        use std::io::{Cursor, Seek, SeekFrom};
+        let mut reader = LimitedCountingReader::new(Cursor::new(b"abcdef"));
+        reader.set_seek_forward(|cursor, n| {
+            cursor.seek(SeekFrom::Current(n as i64)).map(drop)
+        });
+        reader.set_limit(4);
+        assert_eq!(reader.skip(3).unwrap(), 3);
+        let mut buf = [0u8; 1];
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"d");
+        assert!(reader.skip(1).is_err());
+    }
+}
@@ -0,0 +1,114 @@
+//! Re-encoding of text elements with the cheapest text subtype.
+
+use crate::error::Result;
+use crate::header::{ElementType, Header};
+use crate::raw::RawElement;
+use crate::ser::{finalize_header, text_element_type};
+
+/// Re-encode every string of a JSONB value with the cheapest text element
+/// type that can hold it, without changing its content.
+///
+/// Strings stored with escapes (`TextJ` or `Text5`) are decoded and stored
+/// unescaped, as `Text` or `TextRaw`, the same way the serializer stores
+/// strings. Other scalars are copied unchanged, and all headers are
+/// rewritten with their minimal size.
+pub fn normalize_text(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut out = Vec::with_capacity(bytes.len());
+    // containers being written, with the position of their header
+    let mut stack = Vec::new();
+    let mut next = Some(RawElement::read_root(bytes)?);
+    loop {
+        if let Some(element) = next.take() {
+            let element_type = element.element_type();
+            if element.is_container() {
+                stack.push((element.children(), out.len()));
+                out.extend_from_slice(&[u8::from(element_type); 9]);
+            } else if element_type.is_text() {
+                let text: String = element.decode()?;
+                write_element(
+                    &mut out,
+                    text_element_type(&text),
+                    text.as_bytes(),
+                );
+            } else {
+                write_element(&mut out, element_type, element.payload());
+            }
+        }
+        let (children, header_start) = match stack.last_mut() {
+            Some(container) => container,
+            None => return Ok(out),
+        };
+        match children.next().transpose()? {
+            Some(child) => next = Some(child),
+            None => {
+                finalize_header(&mut out, *header_start);
+                stack.pop();
+            }
+        }
+    }
+}
+
+/// Re-encode a JSONB value into the smallest equivalent blob, for storage
+/// optimization: strings get the cheapest text subtype, as with
+/// [`normalize_text`], and every header gets its minimal size, as with
+/// [`canonicalize`](crate::canonicalize), in a single pass.
+///
+/// The result decodes to the same value as the input, as checked by
+/// [`semantic_eq`](crate::semantic_eq).
+pub fn optimize(bytes: &[u8]) -> Result<Vec<u8>> {
+    // normalize_text writes minimal headers for all the elements it copies
+    normalize_text(bytes)
+}
+
+fn write_element(out: &mut Vec<u8>, element_type: ElementType, payload: &[u8]) {
+    let header = Header {
+        element_type,
+        payload_size: payload.len(),
+    };
+    let (head, head_len) = header.to_minimal_bytes();
+    out.extend_from_slice(&head[..head_len]);
+    out.extend_from_slice(payload);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_text() {
+        // "abc", written with unnecessary escapes as a TextJ
+        let bytes = b"\xc8\x0d\\u0061b\\u0063";
+        assert_eq!(normalize_text(bytes).unwrap(), b"\x37abc");
+        // a string that needs escaping in JSON is stored as TextRaw
+        assert_eq!(normalize_text(b"\x28\\n").unwrap(), b"\x1a\n");
+    }
+
+    #[test]
+    fn test_normalize_text_nested() {
+        // {"a": ["b", 1]}
+        let bytes = b"\xcc\x11\x68\\u0061\x9b\x68\\u0062\x131";
+        let normalized = normalize_text(bytes).unwrap();
+        assert_eq!(normalized, b"\x7c\x17a\x4b\x17b\x131");
+        assert_eq!(
+            crate::from_slice::<std::collections::HashMap<String, (String, u8)>>(
+                &normalized
+            ),
+            crate::from_slice(bytes)
+        );
+    }
+
+    #[test]
+    fn test_optimize() {
+        // {"k": ["ab"]}, with escapes and nine-byte headers
+        let bytes = b"\
+            \xfc\x00\x00\x00\x00\x00\x00\x00\x1b\
+            \xf8\x00\x00\x00\x00\x00\x00\x00\x01k\
+            \xfb\x00\x00\x00\x00\x00\x00\x00\x08\
+            \x78a\\u0062";
+        let optimized = optimize(bytes).unwrap();
+        assert_eq!(optimized, b"\x6c\x17k\x3b\x27ab");
+        assert!(optimized.len() < bytes.len());
+        assert!(crate::semantic_eq(bytes, &optimized).unwrap());
+        assert_eq!(optimize(&optimized).unwrap(), optimized);
+    }
+}
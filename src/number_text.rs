@@ -0,0 +1,229 @@
+//! (De)serialization of numbers through their decimal text, for use with
+//! `#[serde(with = "...")]` on fields of arbitrary precision types.
+//!
+//! The text of `Int` and `Float` elements is parsed with [`FromStr`], without
+//! ever going through an `f64`, so no precision is lost. Other elements,
+//! including the JSON5 numbers of `Int5` and `Float5` elements, are rejected
+//! with [`Error::UnexpectedType`](crate::Error::UnexpectedType). This works
+//! with any type that parses numbers from text, including `String`, which
+//! keeps the text as it is:
+//!
+//! ```
+//! #[derive(serde::Deserialize)]
+//! struct Price {
+//!     #[serde(with = "serde_sqlite_jsonb::number_text")]
+//!     amount: String,
+//! }
+//! ```
+//!
+//! The `decimal` module, enabled by the `rust_decimal` feature, applies it
+//! to `rust_decimal::Decimal`.
+//!
+//! Values are serialized from their [`Display`] text, as an `Int` element if
+//! it is an integer, and as a `Float` element otherwise.
+
+use serde::de::{self, Deserializer, Visitor};
+use serde::ser::Serializer;
+use std::fmt::{self, Display};
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+/// The name of the newtype struct that numbers are (de)serialized as, which
+/// the JSONB [`Deserializer`](crate::Deserializer) and
+/// [`Serializer`](crate::Serializer) recognize.
+pub(crate) const TOKEN: &str = "$serde_sqlite_jsonb::private::NumberText";
+
+/// Serialize a value as a number element containing its [`Display`] text,
+/// which must be a valid JSON number.
+pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+where
+    T: Display,
+    S: Serializer,
+{
+    serializer.serialize_newtype_struct(TOKEN, &value.to_string())
+}
+
+/// Deserialize a value by parsing the text of a number element.
+pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: FromStr,
+    T::Err: Display,
+    D: Deserializer<'de>,
+{
+    deserializer
+        .deserialize_newtype_struct(TOKEN, NumberTextVisitor(PhantomData))
+}
+
+struct NumberTextVisitor<T>(PhantomData<T>);
+
+impl<'de, T> Visitor<'de> for NumberTextVisitor<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    type Value = T;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a number")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<T, E> {
+        v.parse().map_err(E::custom)
+    }
+
+    // other formats do not recognize the newtype struct, and decode
+    // numbers into primitive types
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<T, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<T, E> {
+        self.visit_str(&v.to_string())
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<T, E> {
+        self.visit_str(&v.to_string())
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<T, E> {
+        self.visit_str(&v.to_string())
+    }
+}
+
+/// Whether `text` is a number in the JSON (RFC 8259) syntax.
+pub(crate) fn is_json_number(text: &str) -> bool {
+    fn digits(s: &[u8]) -> usize {
+        s.iter().take_while(|c| c.is_ascii_digit()).count()
+    }
+    let mut rest = text.as_bytes();
+    rest = rest.strip_prefix(b"-").unwrap_or(rest);
+    match rest {
+        [b'0', ..] => rest = &rest[1..],
+        [b'1'..=b'9', ..] => rest = &rest[digits(rest)..],
+        _ => return false,
+    }
+    if let Some(fraction) = rest.strip_prefix(b".") {
+        let n = digits(fraction);
+        if n == 0 {
+            return false;
+        }
+        rest = &fraction[n..];
+    }
+    if let Some(exponent) = rest.strip_prefix(b"e").or(rest.strip_prefix(b"E"))
+    {
+        let exponent = match exponent {
+            [b'+' | b'-', e @ ..] => e,
+            e => e,
+        };
+        let n = digits(exponent);
+        if n == 0 {
+            return false;
+        }
+        rest = &exponent[n..];
+    }
+    rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A number type that keeps its exact text, standing for a decimal type.
+    #[derive(Debug, PartialEq)]
+    struct Exact(String);
+
+    impl FromStr for Exact {
+        type Err = String;
+        fn from_str(s: &str) -> Result<Self, String> {
+            if is_json_number(s) {
+                Ok(Exact(s.into()))
+            } else {
+                Err(format!("{s:?} is not a number"))
+            }
+        }
+    }
+
+    impl Display for Exact {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str(&self.0)
+        }
+    }
+
+    #[derive(
+        Debug, PartialEq, serde_derive::Serialize, serde_derive::Deserialize,
+    )]
+    struct Price {
+        #[serde(with = "super")]
+        amount: Exact,
+    }
+
+    fn price(amount: &[u8]) -> Vec<u8> {
+        // {"amount": <amount>}
+        let size = 7 + amount.len() as u8;
+        let mut bytes = if size < 12 {
+            vec![size << 4 | 0xc]
+        } else {
+            vec![0xcc, size]
+        };
+        bytes.extend_from_slice(b"\x6aamount");
+        bytes.extend_from_slice(amount);
+        bytes
+    }
+
+    #[test]
+    fn test_number_text_keeps_exact_value() {
+        let decoded = crate::from_slice::<Price>(&price(b"\x451.10")).unwrap();
+        assert_eq!(decoded.amount, Exact("1.10".into()));
+        let big = b"\xc3\x1e123456789012345678901234567890";
+        let decoded = crate::from_slice::<Price>(&price(big)).unwrap();
+        assert_eq!(decoded.amount.0, "123456789012345678901234567890");
+        assert!(crate::from_slice::<Price>(&price(b"\x171")).is_err());
+    }
+
+    #[test]
+    fn test_number_text_rejects_json5_numbers() {
+        for element in [&b"\x440x1F"[..], b"\x26.5", b"\x86Infinity"] {
+            assert!(matches!(
+                crate::from_slice::<Price>(&price(element)),
+                Err(crate::Error::UnexpectedType {
+                    expected: "a number",
+                    ..
+                })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_number_text_roundtrip() {
+        for (text, element) in [
+            ("1.10", &b"\x451.10"[..]),
+            ("-3", b"\x23-3"),
+            ("2e10", b"\x452e10"),
+        ] {
+            let encoded = crate::to_vec(&Price {
+                amount: Exact(text.into()),
+            })
+            .unwrap();
+            assert_eq!(encoded, price(element));
+            let decoded = crate::from_slice::<Price>(&encoded).unwrap();
+            assert_eq!(decoded.amount.0, text);
+        }
+        let invalid = Price {
+            amount: Exact("1.".into()),
+        };
+        assert!(crate::to_vec(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_is_json_number() {
+        for valid in ["0", "-0", "12", "1.5", "1e5", "1E+5", "-0.25e-3"] {
+            assert!(is_json_number(valid), "{valid}");
+        }
+        for invalid in ["", "-", "01", "1.", ".5", "1e", "+1", "0x1", "NaN"] {
+            assert!(!is_json_number(invalid), "{invalid}");
+        }
+    }
+}
@@ -0,0 +1,501 @@
+//! Conversion of JSONB data back to JSON text.
+
+use crate::error::{Error, Result};
+use crate::header::ElementType;
+use crate::raw::{Children, RawElement};
+use std::fmt::Write;
+
+/// An array or object whose children are being printed.
+struct Frame<'a> {
+    children: Children<'a>,
+    is_object: bool,
+    printed: usize,
+}
+
+/// Convert a JSONB value to a JSON string, like SQLite's `json()` function.
+///
+/// The result is always valid JSON:
+///  - text elements of every type, `TextRaw` included, are decoded and
+///    re-escaped, so that control characters and quotes in their content
+///    cannot produce invalid output,
+///  - the payloads of `Int` and `Float` elements are copied only if they are
+///    JSON numbers, and decoding fails otherwise,
+///  - JSON5 numbers are converted to their JSON equivalent. Infinite floats
+///    are printed as `9e999` and `-9e999`, and NaN as `null`.
+pub fn to_json_string(bytes: &[u8]) -> Result<String> {
+    let mut out = String::new();
+    let mut stack = Vec::new();
+    let root = RawElement::read_root(bytes)?;
+    if let Some(frame) = write_element(root, &mut out, None)? {
+        stack.push(frame);
+    }
+    while let Some(frame) = stack.last_mut() {
+        let child = match frame.children.next().transpose()? {
+            Some(child) => child,
+            None => {
+                if frame.is_object {
+                    if frame.printed % 2 == 1 {
+                        // a key without a value
+                        return Err(Error::MissingObjectValue);
+                    }
+                    out.push('}');
+                } else {
+                    out.push(']');
+                }
+                stack.pop();
+                continue;
+            }
+        };
+        let is_key = frame.is_object && frame.printed % 2 == 0;
+        if frame.printed > 0 {
+            out.push(if is_key || !frame.is_object { ',' } else { ':' });
+        }
+        frame.printed += 1;
+        if is_key && !child.element_type().is_text() {
+            return Err(Error::UnexpectedType {
+                expected: "a text element",
+                found: child.element_type(),
+            });
+        }
+        if let Some(frame) = write_element(child, &mut out, None)? {
+            stack.push(frame);
+        }
+    }
+    Ok(out)
+}
+
+/// Options of [`to_json_string_pretty`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrettyConfig {
+    indent: usize,
+    max_len: Option<usize>,
+    max_depth: Option<usize>,
+}
+
+impl Default for PrettyConfig {
+    fn default() -> Self {
+        PrettyConfig {
+            indent: 2,
+            max_len: None,
+            max_depth: None,
+        }
+    }
+}
+
+impl PrettyConfig {
+    /// Indent by two spaces, without any limit.
+    pub fn new() -> Self {
+        PrettyConfig::default()
+    }
+
+    /// Indent each nesting level by `indent` spaces.
+    pub fn with_indent(mut self, indent: usize) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    /// Stop printing once the output is longer than `max` bytes, and
+    /// replace what goes past them with `...`.
+    pub fn with_max_len(mut self, max: usize) -> Self {
+        self.max_len = Some(max);
+        self
+    }
+
+    /// Print arrays and objects nested in more than `max` others as the
+    /// string `"..."`.
+    pub fn with_max_depth(mut self, max: usize) -> Self {
+        self.max_depth = Some(max);
+        self
+    }
+
+    /// Whether a container at the given depth is printed as `"..."`.
+    fn collapses(&self, element: &RawElement, depth: usize) -> bool {
+        element.is_container()
+            && self.max_depth.map_or(false, |max| depth >= max)
+    }
+
+    /// The number of bytes after which the output passes the length limit,
+    /// if there is one.
+    fn remaining(&self, out: &str) -> Option<usize> {
+        self.max_len.map(|max| (max + 1).saturating_sub(out.len()))
+    }
+
+    /// Start a new line at the given depth.
+    fn newline(&self, depth: usize, out: &mut String) {
+        out.push('\n');
+        out.extend(std::iter::repeat(' ').take(self.indent * depth));
+    }
+
+    /// Cut the output at the length limit, and return whether it was
+    /// reached.
+    fn truncate(&self, out: &mut String) -> bool {
+        match self.max_len {
+            Some(max) if out.len() > max => {
+                let end = (0..=max)
+                    .rev()
+                    .find(|&i| out.is_char_boundary(i))
+                    .unwrap_or(0);
+                out.truncate(end);
+                out.push_str("...");
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Convert a JSONB value to an indented JSON string, for display or
+/// logging, with the same conversions as [`to_json_string`].
+///
+/// The limits of `config` bound the size of the output, whatever the size
+/// of the input: printing stops as soon as the output reaches the length
+/// limit, without reading the rest of the input. The output is then no
+/// longer valid JSON.
+pub fn to_json_string_pretty(
+    bytes: &[u8],
+    config: &PrettyConfig,
+) -> Result<String> {
+    let mut out = String::new();
+    let mut stack = Vec::new();
+    let root = RawElement::read_root(bytes)?;
+    if config.collapses(&root, 0) {
+        out.push_str("\"...\"");
+    } else {
+        let budget = config.remaining(&out);
+        if let Some(frame) = write_element(root, &mut out, budget)? {
+            stack.push(frame);
+        }
+    }
+    loop {
+        // the depth of the children of the innermost container
+        let depth = stack.len();
+        let frame = match stack.last_mut() {
+            Some(frame) => frame,
+            None => break,
+        };
+        if config.truncate(&mut out) {
+            return Ok(out);
+        }
+        let child = match frame.children.next().transpose()? {
+            Some(child) => child,
+            None => {
+                if frame.is_object && frame.printed % 2 == 1 {
+                    // a key without a value
+                    return Err(Error::MissingObjectValue);
+                }
+                let (is_object, printed) = (frame.is_object, frame.printed);
+                stack.pop();
+                if printed > 0 {
+                    config.newline(depth - 1, &mut out);
+                }
+                out.push(if is_object { '}' } else { ']' });
+                continue;
+            }
+        };
+        let is_key = frame.is_object && frame.printed % 2 == 0;
+        if is_key || !frame.is_object {
+            if frame.printed > 0 {
+                out.push(',');
+            }
+            config.newline(depth, &mut out);
+        } else {
+            out.push_str(": ");
+        }
+        frame.printed += 1;
+        if is_key && !child.element_type().is_text() {
+            return Err(Error::UnexpectedType {
+                expected: "a text element",
+                found: child.element_type(),
+            });
+        }
+        if config.collapses(&child, depth) {
+            out.push_str("\"...\"");
+        } else {
+            let budget = config.remaining(&out);
+            if let Some(frame) = write_element(child, &mut out, budget)? {
+                stack.push(frame);
+            }
+        }
+    }
+    config.truncate(&mut out);
+    Ok(out)
+}
+
+/// Print a scalar, or the opening bracket of a container.
+///
+/// With a `budget`, only the start of numbers and strings is printed, which
+/// is enough to write at least `budget` bytes for the ones that are longer.
+fn write_element<'a>(
+    element: RawElement<'a>,
+    out: &mut String,
+    budget: Option<usize>,
+) -> Result<Option<Frame<'a>>> {
+    let cut = |len: usize| budget.map_or(len, |budget| budget.min(len));
+    match element.element_type() {
+        ElementType::Null => out.push_str("null"),
+        ElementType::True => out.push_str("true"),
+        ElementType::False => out.push_str("false"),
+        // JSON numbers are copied as they are. They are ASCII, so cutting
+        // them cannot split a character.
+        ElementType::Int | ElementType::Float => {
+            let text = String::from_utf8(element.payload().to_vec())?;
+            if !crate::number_text::is_json_number(&text) {
+                return Err(Error::Message(format!(
+                    "{text:?} is not a number"
+                )));
+            }
+            out.push_str(&text[..cut(text.len())]);
+        }
+        ElementType::Int5 => {
+            match element.decode::<i128>() {
+                Ok(i) => write!(out, "{i}"),
+                // too large for an i128
+                Err(e) => {
+                    write!(out, "{}", element.decode::<u128>().map_err(|_| e)?)
+                }
+            }
+            .expect("writing to a String cannot fail");
+        }
+        ElementType::Float5 => {
+            let f = element.decode_float()?;
+            if f.is_nan() {
+                out.push_str("null");
+            } else if f.is_infinite() {
+                out.push_str(if f > 0. { "9e999" } else { "-9e999" });
+            } else {
+                write!(out, "{f:?}").expect("writing to a String cannot fail");
+            }
+        }
+        // escaping never makes text shorter, so a prefix of `budget` bytes
+        // is enough
+        ElementType::Text | ElementType::TextRaw => {
+            let payload = element.payload();
+            let prefix = &payload[..cut(payload.len())];
+            match std::str::from_utf8(prefix) {
+                Ok(text) => write_json_string(text, out),
+                // the cut fell inside a character
+                Err(e) if e.error_len().is_none() => write_json_string(
+                    std::str::from_utf8(&prefix[..e.valid_up_to()])
+                        .expect("checked to be valid UTF-8"),
+                    out,
+                ),
+                Err(_) => write_json_string(&element.decode::<String>()?, out),
+            }
+        }
+        ElementType::TextJ | ElementType::Text5 => {
+            let text = element.decode::<String>()?;
+            let end = (0..=cut(text.len()))
+                .rev()
+                .find(|&i| text.is_char_boundary(i))
+                .unwrap_or(0);
+            write_json_string(&text[..end], out);
+        }
+        ElementType::Array | ElementType::Object => {
+            let is_object = element.element_type() == ElementType::Object;
+            out.push(if is_object { '{' } else { '[' });
+            return Ok(Some(Frame {
+                children: element.children(),
+                is_object,
+                printed: 0,
+            }));
+        }
+        t @ (ElementType::Reserved13
+        | ElementType::Reserved14
+        | ElementType::Reserved15) => {
+            return Err(Error::ReservedElementType(t))
+        }
+    }
+    Ok(None)
+}
+
+/// Write `s` as a quoted JSON string literal.
+fn write_json_string(s: &str, out: &mut String) {
+    out.push('"');
+    write_json_escaped(s, out);
+    out.push('"');
+}
+
+/// Write the content of `s`, escaped the way it would be inside a JSON string
+/// literal.
+pub(crate) fn write_json_escaped(s: &str, out: &mut String) {
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if u32::from(c) < 0x20 => {
+                write!(out, "\\u{:04x}", u32::from(c))
+                    .expect("writing to a String cannot fail");
+            }
+            c => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_json_string() {
+        // {"a": [1, 2.5, null], "b": {"c": true}, "d": ""}
+        let bytes =
+            b"\xcc\x13\x17a\x7b\x131\x352.5\x00\x17b\x3c\x17c\x01\x17d\x07";
+        assert_eq!(
+            to_json_string(bytes).unwrap(),
+            r#"{"a":[1,2.5,null],"b":{"c":true},"d":""}"#
+        );
+    }
+
+    #[test]
+    fn test_to_json_string_text_raw_is_escaped() {
+        // a TextRaw payload is stored as is, without any escaping
+        let bytes = b"\x6aa\x01\"\\\nb";
+        let json = to_json_string(bytes).unwrap();
+        assert_eq!(json, r#""a\u0001\"\\\nb""#);
+        #[cfg(feature = "serde_json")]
+        assert_eq!(
+            serde_json::from_str::<String>(&json).unwrap(),
+            "a\u{1}\"\\\nb"
+        );
+    }
+
+    #[test]
+    fn test_to_json_string_text_j() {
+        // TextJ escapes are decoded, then written back in canonical form
+        assert_eq!(to_json_string(b"\x88\\u0041\\n").unwrap(), r#""A\n""#);
+    }
+
+    #[test]
+    #[cfg(feature = "serde_json5")]
+    fn test_to_json_string_json5_numbers() {
+        // [0x1F, Infinity, .5]
+        let bytes = b"\xcb\x11\x440x1F\x86Infinity\x26.5";
+        assert_eq!(to_json_string(bytes).unwrap(), "[31,9e999,0.5]");
+    }
+
+    #[test]
+    #[cfg(feature = "serde_json5")]
+    fn test_to_json_string_large_int5() {
+        // 0x8000000000000000 and 0xFFFFFFFFFFFFFFFF
+        assert_eq!(
+            to_json_string(b"\xc4\x120x8000000000000000").unwrap(),
+            "9223372036854775808"
+        );
+        assert_eq!(
+            to_json_string(b"\xc4\x120xFFFFFFFFFFFFFFFF").unwrap(),
+            u64::MAX.to_string()
+        );
+        assert_eq!(
+            to_json_string(b"\xc4\x13-0x8000000000000000").unwrap(),
+            i64::MIN.to_string()
+        );
+    }
+
+    #[test]
+    fn test_to_json_string_invalid_number() {
+        for bytes in [&b"\x23ab"[..], b"\x2301", b"\x251.", b"\x35NaN"] {
+            assert!(to_json_string(bytes).is_err(), "{bytes:?}");
+        }
+        assert_eq!(to_json_string(b"\x451e-5").unwrap(), "1e-5");
+    }
+
+    #[test]
+    fn test_to_json_string_invalid_object() {
+        // {1: 2}
+        assert_eq!(
+            to_json_string(b"\x4c\x131\x132"),
+            Err(Error::UnexpectedType {
+                expected: "a text element",
+                found: ElementType::Int,
+            })
+        );
+        // {"a"}
+        assert_eq!(
+            to_json_string(b"\x2c\x17a"),
+            Err(Error::MissingObjectValue)
+        );
+    }
+
+    // {"a": [1, 2], "b": {}}
+    const NESTED: &[u8] = b"\xac\x17a\x4b\x131\x132\x17b\x0c";
+
+    #[test]
+    fn test_to_json_string_pretty() {
+        assert_eq!(
+            to_json_string_pretty(NESTED, &PrettyConfig::new()).unwrap(),
+            "{\n  \"a\": [\n    1,\n    2\n  ],\n  \"b\": {}\n}"
+        );
+        let config = PrettyConfig::new().with_indent(0);
+        assert_eq!(
+            to_json_string_pretty(b"\x2b\x131", &config).unwrap(),
+            "[\n1\n]"
+        );
+        assert_eq!(to_json_string_pretty(b"\x17a", &config).unwrap(), "\"a\"");
+    }
+
+    #[test]
+    fn test_to_json_string_pretty_max_len() {
+        let config = PrettyConfig::new().with_max_len(10);
+        assert_eq!(
+            to_json_string_pretty(NESTED, &config).unwrap(),
+            "{\n  \"a\": [..."
+        );
+        // the rest of the input is not read once the limit is reached:
+        // [[1], <reserved>]
+        let bytes = b"\x4b\x2b\x131\x0d";
+        let config = PrettyConfig::new().with_max_len(2);
+        assert_eq!(to_json_string_pretty(bytes, &config).unwrap(), "[\n...");
+        assert!(to_json_string_pretty(bytes, &PrettyConfig::new()).is_err());
+        // limits fall on character boundaries
+        let config = PrettyConfig::new().with_max_len(2);
+        assert_eq!(
+            to_json_string_pretty("\x27é".as_bytes(), &config).unwrap(),
+            "\"..."
+        );
+    }
+
+    #[test]
+    fn test_to_json_string_pretty_max_len_cuts_scalars() {
+        // ["é\\u00e9é…", "ééé…", 12345678901234567890, "\\\"\\\"…"]
+        let mut bytes = b"\xcb\xa4\xc7\x3c".to_vec();
+        bytes.extend_from_slice("é\\u00e9é".repeat(6).as_bytes());
+        bytes.extend_from_slice(b"\xc8\x3c");
+        bytes.extend_from_slice("é\\u00e9é".repeat(6).as_bytes());
+        bytes.extend_from_slice(b"\xc3\x14");
+        bytes.extend_from_slice("1234567890".repeat(2).as_bytes());
+        bytes.extend_from_slice(b"\xca\x10");
+        bytes.extend_from_slice("\\\"".repeat(8).as_bytes());
+        let full = to_json_string_pretty(&bytes, &PrettyConfig::new()).unwrap();
+        for max in 0..full.len() + 2 {
+            let config = PrettyConfig::new().with_max_len(max);
+            let mut expected = full.clone();
+            config.truncate(&mut expected);
+            assert_eq!(
+                to_json_string_pretty(&bytes, &config).unwrap(),
+                expected,
+                "{max}"
+            );
+        }
+        // invalid UTF-8 past the limit is not read
+        let bytes = b"\x87abcd\xff\xff\xff\xff";
+        assert!(to_json_string_pretty(bytes, &PrettyConfig::new()).is_err());
+        let config = PrettyConfig::new().with_max_len(3);
+        assert_eq!(to_json_string_pretty(bytes, &config).unwrap(), "\"ab...");
+        let config = PrettyConfig::new().with_max_len(5);
+        assert!(to_json_string_pretty(bytes, &config).is_err());
+    }
+
+    #[test]
+    fn test_to_json_string_pretty_max_depth() {
+        let config = PrettyConfig::new().with_max_depth(1);
+        assert_eq!(
+            to_json_string_pretty(NESTED, &config).unwrap(),
+            "{\n  \"a\": \"...\",\n  \"b\": \"...\"\n}"
+        );
+        let config = PrettyConfig::new().with_max_depth(0);
+        assert_eq!(to_json_string_pretty(NESTED, &config).unwrap(), "\"...\"");
+    }
+}
@@ -0,0 +1,499 @@
+//! Traversal of JSONB data without decoding it.
+//!
+//! Every element starts with a header that contains the size of its payload,
+//! so the structure of a blob can be walked, and whole values skipped,
+//! without ever parsing the payloads themselves.
+
+use crate::error::{Error, Result};
+use crate::header::{ElementType, Header};
+use serde::de::{Deserialize, DeserializeOwned};
+use std::collections::BTreeMap;
+
+/// A single element (header and payload) inside a byte slice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RawElement<'a> {
+    pub(crate) header: Header,
+    /// The bytes of the element, header included
+    pub(crate) bytes: &'a [u8],
+    /// The length of the header at the start of `bytes`
+    pub(crate) header_len: usize,
+}
+
+impl<'a> RawElement<'a> {
+    /// Split the first element off the start of `input`.
+    pub(crate) fn read(input: &mut &'a [u8]) -> Result<Self> {
+        RawElement::read_checked(input, false)
+    }
+
+    /// Like [`Self::read`], but also returning elements of reserved types,
+    /// with their payload left uninterpreted, when `allow_reserved` is set.
+    fn read_checked(
+        input: &mut &'a [u8],
+        allow_reserved: bool,
+    ) -> Result<Self> {
+        let (&first_byte, rest) = input.split_first().ok_or(Error::Empty)?;
+        let size_len = Header::size_field_len(first_byte);
+        if rest.len() < size_len {
+            return Err(Error::TruncatedHeader {
+                expected: size_len,
+                got: rest.len(),
+            });
+        }
+        let header = Header::from_parts(first_byte, &rest[..size_len]);
+        if header.element_type.is_reserved() && !allow_reserved {
+            return Err(Error::ReservedElementType(header.element_type));
+        }
+        let header_len = 1 + size_len;
+        let available = input.len() - header_len;
+        if header.payload_size > available {
+            return Err(Error::UnexpectedEof);
+        }
+        let (bytes, rest) = input.split_at(header_len + header.payload_size);
+        *input = rest;
+        Ok(RawElement {
+            header,
+            bytes,
+            header_len,
+        })
+    }
+
+    /// Read the single element that spans all of `input`.
+    pub(crate) fn read_root(input: &'a [u8]) -> Result<Self> {
+        RawElement::read_root_checked(input, false)
+    }
+
+    fn read_root_checked(
+        mut input: &'a [u8],
+        allow_reserved: bool,
+    ) -> Result<Self> {
+        let element = RawElement::read_checked(&mut input, allow_reserved)?;
+        match input.first() {
+            None => Ok(element),
+            Some(&first_byte) => Err(Error::TrailingCharacters {
+                remaining: input.len(),
+                next_element_type: ElementType::from_header_byte(first_byte),
+            }),
+        }
+    }
+
+    pub(crate) fn element_type(&self) -> ElementType {
+        self.header.element_type
+    }
+
+    pub(crate) fn is_container(&self) -> bool {
+        matches!(
+            self.element_type(),
+            ElementType::Array | ElementType::Object
+        )
+    }
+
+    pub(crate) fn payload(&self) -> &'a [u8] {
+        &self.bytes[self.header_len..]
+    }
+
+    /// Iterate over the elements contained in the payload of an array or
+    /// an object. For an object, keys and values alternate.
+    pub(crate) fn children(&self) -> Children<'a> {
+        Children {
+            remaining: self.payload(),
+            allow_reserved: false,
+        }
+    }
+
+    /// Decode the element into a value.
+    pub(crate) fn decode<T: Deserialize<'a>>(&self) -> Result<T> {
+        crate::from_slice(self.bytes)
+    }
+
+    /// Decode a number element into a float, accepting NaN and infinities.
+    pub(crate) fn decode_float(&self) -> Result<f64> {
+        let mut de = crate::Deserializer::from_bytes(self.bytes)
+            .with_allow_non_finite(true);
+        let f = f64::deserialize(&mut de)?;
+        de.end()?;
+        Ok(f)
+    }
+}
+
+/// Iterator over the elements directly contained in an array or object.
+pub(crate) struct Children<'a> {
+    remaining: &'a [u8],
+    allow_reserved: bool,
+}
+
+impl<'a> Iterator for Children<'a> {
+    type Item = Result<RawElement<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining.is_empty() {
+            return None;
+        }
+        let element =
+            RawElement::read_checked(&mut self.remaining, self.allow_reserved);
+        let element = element.map_err(|e| {
+            // do not yield anything after an error
+            self.remaining = &[];
+            match e {
+                Error::UnexpectedEof | Error::TruncatedHeader { .. } => {
+                    Error::ElementExceedsContainer
+                }
+                e => e,
+            }
+        });
+        Some(element)
+    }
+}
+
+/// Call `visit` on every element of `bytes`, in order, along with the number
+/// of arrays and objects that enclose it.
+///
+/// The traversal uses an explicit stack rather than recursion, so deeply
+/// nested input cannot overflow the call stack.
+pub(crate) fn walk<'a>(
+    bytes: &'a [u8],
+    visit: impl FnMut(RawElement<'a>, usize) -> Result<()>,
+) -> Result<()> {
+    walk_checked(bytes, false, visit)
+}
+
+/// Like [`walk`], but also visiting elements of reserved types, without
+/// looking into their payload, when `allow_reserved` is set.
+pub(crate) fn walk_checked<'a>(
+    bytes: &'a [u8],
+    allow_reserved: bool,
+    mut visit: impl FnMut(RawElement<'a>, usize) -> Result<()>,
+) -> Result<()> {
+    let root = RawElement::read_root_checked(bytes, allow_reserved)?;
+    visit(root, 0)?;
+    let mut stack = Vec::new();
+    let children_of = |element: RawElement<'a>| Children {
+        allow_reserved,
+        ..element.children()
+    };
+    if root.is_container() {
+        stack.push(children_of(root));
+    }
+    while let Some(children) = stack.last_mut() {
+        match children.next() {
+            None => {
+                stack.pop();
+            }
+            Some(child) => {
+                let child = child?;
+                visit(child, stack.len())?;
+                if child.is_container() {
+                    stack.push(children_of(child));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Deserialize the value associated with `key` in a JSONB object,
+/// without decoding any of the other values.
+///
+/// Returns `Ok(None)` if the object does not contain the key. If the key
+/// appears several times, the first occurrence is used.
+pub fn get_field<'a, T>(bytes: &'a [u8], key: &str) -> Result<Option<T>>
+where
+    T: Deserialize<'a>,
+{
+    let root = RawElement::read_root(bytes)?;
+    if root.element_type() != ElementType::Object {
+        return Err(Error::UnexpectedType {
+            expected: "Object",
+            found: root.element_type(),
+        });
+    }
+    let mut children = root.children();
+    while let Some(k) = children.next().transpose()? {
+        let value = children
+            .next()
+            .transpose()?
+            .ok_or(Error::MissingObjectValue)?;
+        if k.decode::<String>()? == key {
+            return value.decode().map(Some);
+        }
+    }
+    Ok(None)
+}
+
+/// List the keys of a JSONB object, in order, without decoding any of the
+/// values.
+///
+/// Keys that appear several times are listed at each occurrence.
+pub fn object_keys(bytes: &[u8]) -> Result<Vec<String>> {
+    let root = RawElement::read_root(bytes)?;
+    if root.element_type() != ElementType::Object {
+        return Err(Error::UnexpectedType {
+            expected: "Object",
+            found: root.element_type(),
+        });
+    }
+    let mut keys = Vec::new();
+    let mut children = root.children();
+    while let Some(k) = children.next().transpose()? {
+        children
+            .next()
+            .transpose()?
+            .ok_or(Error::MissingObjectValue)?;
+        keys.push(k.decode::<String>()?);
+    }
+    Ok(keys)
+}
+
+/// Deserialize the values of a JSONB object, in order, ignoring its keys.
+pub fn object_values<T: DeserializeOwned>(bytes: &[u8]) -> Result<Vec<T>> {
+    let root = RawElement::read_root(bytes)?;
+    if root.element_type() != ElementType::Object {
+        return Err(Error::UnexpectedType {
+            expected: "Object",
+            found: root.element_type(),
+        });
+    }
+    let mut values = Vec::new();
+    let mut children = root.children();
+    while children.next().transpose()?.is_some() {
+        let value = children
+            .next()
+            .transpose()?
+            .ok_or(Error::MissingObjectValue)?;
+        values.push(value.decode()?);
+    }
+    Ok(values)
+}
+
+/// Build a map from a JSONB array of `[key, value]` pairs, the usual way to
+/// store a map whose keys are not strings, such as `[[1, "a"], [2, "b"]]`.
+///
+/// When a key appears several times, the last value is kept.
+pub fn pairs_to_map<K, V>(bytes: &[u8]) -> Result<BTreeMap<K, V>>
+where
+    K: DeserializeOwned + Ord,
+    V: DeserializeOwned,
+{
+    let root = RawElement::read_root(bytes)?;
+    if root.element_type() != ElementType::Array {
+        return Err(Error::UnexpectedType {
+            expected: "Array",
+            found: root.element_type(),
+        });
+    }
+    let mut map = BTreeMap::new();
+    for pair in root.children() {
+        let (key, value) = pair?.decode()?;
+        map.insert(key, value);
+    }
+    Ok(map)
+}
+
+/// Iterate over the elements of a JSONB array without decoding them.
+///
+/// Each item is the raw bytes of one element, header included, which can
+/// itself be passed to [`from_slice`](crate::from_slice). Elements are split
+/// off lazily, so an error in a later element is only reported when the
+/// iterator reaches it.
+pub fn iter_array_raw(
+    bytes: &[u8],
+) -> Result<impl Iterator<Item = Result<&[u8]>>> {
+    let root = RawElement::read_root(bytes)?;
+    if root.element_type() != ElementType::Array {
+        return Err(Error::UnexpectedType {
+            expected: "Array",
+            found: root.element_type(),
+        });
+    }
+    Ok(root.children().map(|child| child.map(|c| c.bytes)))
+}
+
+/// Check that a JSONB value can be converted to standard JSON without a
+/// JSON5 parser, by looking at the type of each of its elements.
+///
+/// Returns [`Error::Json5ElementPresent`] with the type of the first `Int5`,
+/// `Float5` or `Text5` element that is found. Payloads are not validated.
+pub fn assert_strict_json(bytes: &[u8]) -> Result<()> {
+    walk(bytes, |element, _depth| match element.element_type() {
+        t @ (ElementType::Int5 | ElementType::Float5 | ElementType::Text5) => {
+            Err(Error::Json5ElementPresent(t))
+        }
+        _ => Ok(()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // {"a": 1, "b": [true], "c": "x"}
+    const OBJECT: &[u8] = b"\xcc\x0c\x17a\x131\x17b\x1b\x01\x17c\x17x";
+
+    #[test]
+    fn test_get_field_present() {
+        assert_eq!(get_field::<u8>(OBJECT, "a").unwrap(), Some(1));
+        assert_eq!(
+            get_field::<Vec<bool>>(OBJECT, "b").unwrap(),
+            Some(vec![true])
+        );
+        assert_eq!(get_field::<String>(OBJECT, "c").unwrap(), Some("x".into()));
+    }
+
+    #[test]
+    fn test_get_field_absent() {
+        assert_eq!(get_field::<u8>(OBJECT, "d").unwrap(), None);
+        assert_eq!(get_field::<u8>(b"\x0c", "a").unwrap(), None);
+    }
+
+    #[test]
+    fn test_get_field_skips_values_without_decoding() {
+        // {"a": <invalid int>, "b": 2}
+        let bytes = b"\x9c\x17a\x23xx\x17b\x132";
+        assert_eq!(get_field::<u8>(bytes, "b").unwrap(), Some(2));
+    }
+
+    #[test]
+    fn test_children_exceeding_container() {
+        let array = RawElement::read_root(b"\x2b\x57h").unwrap();
+        let children: Vec<_> = array.children().collect();
+        assert_eq!(children, [Err(Error::ElementExceedsContainer)]);
+    }
+
+    #[test]
+    fn test_get_field_not_an_object() {
+        assert_eq!(
+            get_field::<u8>(b"\x0b", "a"),
+            Err(Error::UnexpectedType {
+                expected: "Object",
+                found: ElementType::Array,
+            })
+        );
+    }
+
+    #[test]
+    fn test_walk() {
+        // [1, [true], {}]
+        let mut visited = vec![];
+        walk(b"\x5b\x131\x1b\x01\x0c", |element, depth| {
+            visited.push((element.element_type(), depth));
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(
+            visited,
+            [
+                (ElementType::Array, 0),
+                (ElementType::Int, 1),
+                (ElementType::Array, 1),
+                (ElementType::True, 2),
+                (ElementType::Object, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_iter_array_raw() {
+        // [1, "ab", [true]]
+        let bytes = b"\x7b\x131\x27ab\x1b\x01";
+        let elements: Vec<&[u8]> =
+            iter_array_raw(bytes).unwrap().map(Result::unwrap).collect();
+        assert_eq!(elements, [&b"\x131"[..], b"\x27ab", b"\x1b\x01"]);
+        assert_eq!(crate::from_slice::<Vec<bool>>(elements[2]), Ok(vec![true]));
+    }
+
+    #[test]
+    fn test_iter_array_raw_errors() {
+        assert_eq!(
+            iter_array_raw(b"\x0c").err(),
+            Some(Error::UnexpectedType {
+                expected: "Array",
+                found: ElementType::Object,
+            })
+        );
+        let elements: Vec<_> =
+            iter_array_raw(b"\x3b\x131\x57").unwrap().collect();
+        assert_eq!(
+            elements,
+            [Ok(&b"\x131"[..]), Err(Error::ElementExceedsContainer)]
+        );
+    }
+
+    #[test]
+    fn test_malformation_errors() {
+        assert_eq!(
+            RawElement::read_root(b"\x57hel"),
+            Err(Error::UnexpectedEof)
+        );
+        assert_eq!(
+            RawElement::read_root(b"\x0e"),
+            Err(Error::ReservedElementType(ElementType::Reserved14))
+        );
+        assert_eq!(
+            RawElement::read_root(b"\x00\x00"),
+            Err(Error::TrailingCharacters {
+                remaining: 1,
+                next_element_type: ElementType::Null
+            })
+        );
+    }
+
+    #[test]
+    fn test_assert_strict_json() {
+        // {"a": [1, 2.5, "x\\n"]}
+        let strict = b"\xcc\x0d\x17a\xab\x131\x352.5\x38x\\n";
+        assert_eq!(assert_strict_json(strict), Ok(()));
+        // {"a": [1, 0x1F]}
+        let json5 = b"\xac\x17a\x7b\x131\x440x1F";
+        assert_eq!(
+            assert_strict_json(json5),
+            Err(Error::Json5ElementPresent(ElementType::Int5))
+        );
+    }
+
+    #[test]
+    fn test_object_keys() {
+        assert_eq!(object_keys(OBJECT).unwrap(), ["a", "b", "c"]);
+        // {"a": <invalid int>, "b": 2, "a": null}, with keys of several
+        // text types
+        let bytes = b"\xcc\x11\x17a\x23xx\x1ab\x132\x68\\u0061\x00";
+        assert_eq!(object_keys(bytes).unwrap(), ["a", "b", "a"]);
+        assert_eq!(object_keys(b"\x0c").unwrap(), Vec::<String>::new());
+        assert_eq!(
+            object_keys(b"\x131"),
+            Err(Error::UnexpectedType {
+                expected: "Object",
+                found: ElementType::Int,
+            })
+        );
+        assert_eq!(object_keys(b"\x2c\x17a"), Err(Error::MissingObjectValue));
+    }
+
+    #[test]
+    fn test_object_values() {
+        // {"a": 1, "b": 2, "a": 3}
+        let bytes = b"\xcc\x0c\x17a\x131\x17b\x132\x17a\x133";
+        assert_eq!(object_values::<i64>(bytes).unwrap(), vec![1, 2, 3]);
+        assert_eq!(object_values::<u8>(b"\x0c").unwrap(), Vec::<u8>::new());
+        assert_eq!(
+            object_values::<u8>(b"\x2b\x131"),
+            Err(Error::UnexpectedType {
+                expected: "Object",
+                found: ElementType::Array,
+            })
+        );
+        // {"a": 1, "b": "x"}
+        assert!(object_values::<u8>(b"\x8c\x17a\x131\x17b\x17x").is_err());
+    }
+
+    #[test]
+    fn test_pairs_to_map() {
+        // [[1, "a"], [2, "b"], [1, "c"]]
+        let bytes = b"\xcb\x0f\x4b\x131\x17a\x4b\x132\x17b\x4b\x131\x17c";
+        let map = pairs_to_map::<u32, String>(bytes).unwrap();
+        assert_eq!(map, BTreeMap::from([(1, "c".into()), (2, "b".into())]));
+        assert!(pairs_to_map::<u32, u8>(b"\x0b").unwrap().is_empty());
+        // [[1]]
+        assert!(pairs_to_map::<u32, u8>(b"\x3b\x2b\x131").is_err());
+        // {"a": 1}
+        assert!(pairs_to_map::<String, u8>(b"\x4c\x17a\x131").is_err());
+    }
+}
@@ -0,0 +1,151 @@
+//! Capture of a JSONB element without decoding it.
+
+use crate::error;
+use crate::raw::RawElement;
+use serde::de::{self, Deserialize, DeserializeOwned, Deserializer, Visitor};
+use serde::ser::{Serialize, Serializer};
+use std::fmt;
+
+/// The name of the newtype struct that a [`JsonbRawValue`] (de)serializes
+/// as, which the JSONB [`Deserializer`](crate::Deserializer) and
+/// [`Serializer`](crate::Serializer) recognize.
+pub(crate) const TOKEN: &str = "$serde_sqlite_jsonb::private::RawValue";
+
+/// The bytes of a single JSONB element, header included, left undecoded.
+///
+/// Declaring a field as `JsonbRawValue` makes the deserializer copy the
+/// element as it is, so that it can be decoded later, or serialized again
+/// byte for byte.
+///
+/// ```
+/// use serde_sqlite_jsonb::JsonbRawValue;
+///
+/// #[derive(serde::Deserialize)]
+/// struct Envelope {
+///     kind: String,
+///     payload: JsonbRawValue,
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct JsonbRawValue {
+    bytes: Vec<u8>,
+}
+
+impl JsonbRawValue {
+    /// Wrap the bytes of a JSONB element, checking that they contain
+    /// exactly one element.
+    pub fn from_bytes(bytes: Vec<u8>) -> error::Result<Self> {
+        RawElement::read_root(&bytes)?;
+        Ok(JsonbRawValue { bytes })
+    }
+
+    /// The bytes of the element.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Unwrap the bytes of the element.
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    /// Decode the element into a value.
+    pub fn decode<T: DeserializeOwned>(&self) -> error::Result<T> {
+        crate::from_slice(&self.bytes)
+    }
+}
+
+impl Serialize for JsonbRawValue {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_newtype_struct(TOKEN, &Bytes(&self.bytes))
+    }
+}
+
+/// Serializes with `serialize_bytes`, rather than as a sequence.
+struct Bytes<'a>(&'a [u8]);
+
+impl Serialize for Bytes<'_> {
+    fn serialize<S: Serializer>(
+        &self,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for JsonbRawValue {
+    fn deserialize<D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        deserializer.deserialize_newtype_struct(TOKEN, RawValueVisitor)
+    }
+}
+
+struct RawValueVisitor;
+
+impl<'de> Visitor<'de> for RawValueVisitor {
+    type Value = JsonbRawValue;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a JSONB element")
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<JsonbRawValue, E> {
+        self.visit_byte_buf(v.to_vec())
+    }
+
+    fn visit_byte_buf<E: de::Error>(
+        self,
+        v: Vec<u8>,
+    ) -> Result<JsonbRawValue, E> {
+        JsonbRawValue::from_bytes(v).map_err(E::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(
+        Debug, PartialEq, serde_derive::Serialize, serde_derive::Deserialize,
+    )]
+    struct Envelope {
+        kind: String,
+        payload: JsonbRawValue,
+    }
+
+    // {"kind": "a", "payload": {"x": [1]}}, with a non-minimal header
+    // on the payload
+    const ENVELOPE: &[u8] =
+        b"\xcc\x17\x4akind\x1aa\x7apayload\xdc\x00\x05\x1ax\x2b\x131";
+
+    #[test]
+    fn test_raw_value_captures_nested_object() {
+        let envelope = crate::from_slice::<Envelope>(ENVELOPE).unwrap();
+        assert_eq!(envelope.kind, "a");
+        assert_eq!(envelope.payload.as_bytes(), b"\xdc\x00\x05\x1ax\x2b\x131");
+        let payload: std::collections::HashMap<String, Vec<u8>> =
+            envelope.payload.decode().unwrap();
+        assert_eq!(payload["x"], [1]);
+        let envelope = crate::from_reader::<_, Envelope>(ENVELOPE).unwrap();
+        assert_eq!(envelope.payload.into_bytes()[..3], *b"\xdc\x00\x05");
+    }
+
+    #[test]
+    fn test_raw_value_serialized_verbatim() {
+        let envelope = crate::from_slice::<Envelope>(ENVELOPE).unwrap();
+        assert_eq!(crate::to_vec(&envelope).unwrap(), ENVELOPE);
+        let raw: Option<JsonbRawValue> = crate::from_slice(b"\x17a").unwrap();
+        assert_eq!(raw.unwrap().as_bytes(), b"\x17a");
+    }
+
+    #[test]
+    fn test_raw_value_from_bytes() {
+        assert!(JsonbRawValue::from_bytes(b"\x131".to_vec()).is_ok());
+        assert!(JsonbRawValue::from_bytes(b"\x131\x00".to_vec()).is_err());
+        assert!(JsonbRawValue::from_bytes(b"\x23".to_vec()).is_err());
+    }
+}
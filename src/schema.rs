@@ -0,0 +1,261 @@
+//! Inference of the structure shared by the values of a JSONB document.
+
+use crate::error::{Error, Result};
+use crate::header::ElementType;
+use crate::int_width::{min_int_type, IntWidth};
+use crate::raw::{walk, RawElement};
+
+/// The structure of a JSONB value, as inferred by [`infer_schema`], to help
+/// writing the Rust types that it decodes into.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schema {
+    /// No value was seen, as for the elements of an empty array
+    Unknown,
+    Null,
+    Bool,
+    /// An integer, with the smallest type that holds all the values seen
+    Int(IntWidth),
+    Float,
+    String,
+    /// An array, with the schema of all its elements
+    Array(Box<Schema>),
+    /// An object, with its fields in the order they were first seen
+    Object(Vec<(String, Schema)>),
+    /// A value that is sometimes null or missing
+    Optional(Box<Schema>),
+    /// Values of incompatible types
+    Mixed,
+}
+
+impl Schema {
+    /// The schema of values that are either of `self` or of `other`.
+    fn merge(self, other: Schema) -> Schema {
+        use Schema::*;
+        match (self, other) {
+            (Unknown, s) | (s, Unknown) => s,
+            (Null, Null) => Null,
+            (Null, Optional(s)) | (Optional(s), Null) => Optional(s),
+            (Null, s) | (s, Null) => Optional(Box::new(s)),
+            (Optional(a), Optional(b)) => Optional(Box::new(a.merge(*b))),
+            (Optional(a), b) | (b, Optional(a)) => {
+                Optional(Box::new(a.merge(b)))
+            }
+            (Int(a), Int(b)) => Int(a.union(b)),
+            (Int(_) | Float, Int(_) | Float) => Float,
+            (Array(a), Array(b)) => Array(Box::new(a.merge(*b))),
+            (Object(a), Object(b)) => Object(merge_fields(a, b)),
+            (a, b) if a == b => a,
+            _ => Mixed,
+        }
+    }
+
+    /// The schema of a field that may be missing.
+    fn optional(self) -> Schema {
+        self.merge(Schema::Null)
+    }
+}
+
+/// Merge the fields of two object schemas, making optional the fields that
+/// only one of them has.
+fn merge_fields(
+    a: Vec<(String, Schema)>,
+    b: Vec<(String, Schema)>,
+) -> Vec<(String, Schema)> {
+    let mut b: Vec<Option<(String, Schema)>> =
+        b.into_iter().map(Some).collect();
+    let mut fields: Vec<(String, Schema)> = a
+        .into_iter()
+        .map(|(key, schema)| {
+            let other = b
+                .iter_mut()
+                .find(|field| matches!(field, Some((k, _)) if *k == key))
+                .and_then(Option::take);
+            match other {
+                Some((_, other)) => (key, schema.merge(other)),
+                None => (key, schema.optional()),
+            }
+        })
+        .collect();
+    fields.extend(
+        b.into_iter()
+            .flatten()
+            .map(|(key, schema)| (key, schema.optional())),
+    );
+    fields
+}
+
+/// A container whose schema is being built.
+enum Partial {
+    /// An array, with the merged schema of its elements so far
+    Array(Schema),
+    /// An object, with its fields so far, and the key of the value that
+    /// comes next, once it has been read
+    Object {
+        fields: Vec<(String, Schema)>,
+        key: Option<String>,
+    },
+}
+
+impl Partial {
+    /// Add the schema of the next element.
+    fn add(&mut self, schema: Schema) {
+        match self {
+            Partial::Array(items) => {
+                *items = std::mem::replace(items, Schema::Unknown).merge(schema)
+            }
+            Partial::Object { fields, key } => {
+                let key = key.take().expect("values come after their key");
+                match fields.iter_mut().find(|(k, _)| *k == key) {
+                    // a key that appears twice in the same object
+                    Some((_, field)) => {
+                        *field = std::mem::replace(field, Schema::Unknown)
+                            .merge(schema)
+                    }
+                    None => fields.push((key, schema)),
+                }
+            }
+        }
+    }
+
+    fn finish(self) -> Result<Schema> {
+        match self {
+            Partial::Array(items) => Ok(Schema::Array(Box::new(items))),
+            Partial::Object { key: Some(_), .. } => {
+                Err(Error::MissingObjectValue)
+            }
+            Partial::Object { fields, .. } => Ok(Schema::Object(fields)),
+        }
+    }
+}
+
+/// The schema of a scalar element.
+fn scalar_schema(element: RawElement) -> Result<Schema> {
+    Ok(match element.element_type() {
+        ElementType::Null => Schema::Null,
+        ElementType::True | ElementType::False => Schema::Bool,
+        ElementType::Int | ElementType::Int5 => {
+            Schema::Int(min_int_type(element.bytes)?)
+        }
+        ElementType::Float | ElementType::Float5 => Schema::Float,
+        t if t.is_text() => Schema::String,
+        t => unreachable!("{t:?} is not a scalar type"),
+    })
+}
+
+/// Infer the structure of a JSONB value, in a single pass over it.
+///
+/// The elements of an array are described by a single schema, that merges
+/// those of all the elements: integers get the smallest type that holds all
+/// of them, integers mixed with floats are floats, and the fields of
+/// objects that are missing or null in some of them are optional. Values of
+/// types that cannot be merged are [`Schema::Mixed`].
+pub fn infer_schema(bytes: &[u8]) -> Result<Schema> {
+    // the containers that enclose the current element, outermost first
+    let mut stack: Vec<Partial> = Vec::new();
+    let mut root = None;
+    walk(bytes, |element, depth| {
+        finish_containers(&mut stack, &mut root, depth)?;
+        if let Some(Partial::Object {
+            key: key @ None, ..
+        }) = stack.last_mut()
+        {
+            if !element.element_type().is_text() {
+                return Err(Error::UnexpectedType {
+                    expected: "a text element",
+                    found: element.element_type(),
+                });
+            }
+            *key = Some(element.decode()?);
+            return Ok(());
+        }
+        match element.element_type() {
+            ElementType::Array => stack.push(Partial::Array(Schema::Unknown)),
+            ElementType::Object => stack.push(Partial::Object {
+                fields: Vec::new(),
+                key: None,
+            }),
+            _ => add(&mut stack, &mut root, scalar_schema(element)?),
+        }
+        Ok(())
+    })?;
+    finish_containers(&mut stack, &mut root, 0)?;
+    Ok(root.expect("the root has been visited"))
+}
+
+/// Finish the containers nested deeper than `depth`, which have no element
+/// left.
+fn finish_containers(
+    stack: &mut Vec<Partial>,
+    root: &mut Option<Schema>,
+    depth: usize,
+) -> Result<()> {
+    while stack.len() > depth {
+        let container = stack.pop().expect("the stack is deeper than depth");
+        add(stack, root, container.finish()?);
+    }
+    Ok(())
+}
+
+/// Add the schema of an element to its container, or make it the root.
+fn add(stack: &mut [Partial], root: &mut Option<Schema>, schema: Schema) {
+    match stack.last_mut() {
+        Some(container) => container.add(schema),
+        None => *root = Some(schema),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn optional(schema: Schema) -> Schema {
+        Schema::Optional(Box::new(schema))
+    }
+
+    #[test]
+    fn test_infer_schema_scalars() {
+        assert_eq!(infer_schema(b"\x00").unwrap(), Schema::Null);
+        assert_eq!(infer_schema(b"\x02").unwrap(), Schema::Bool);
+        assert_eq!(infer_schema(b"\x23-1").unwrap(), Schema::Int(IntWidth::I8));
+        assert_eq!(infer_schema(b"\x351.5").unwrap(), Schema::Float);
+        assert_eq!(infer_schema(b"\x18x").unwrap(), Schema::String);
+        assert_eq!(
+            infer_schema(b"\x0b").unwrap(),
+            Schema::Array(Box::new(Schema::Unknown))
+        );
+    }
+
+    #[test]
+    fn test_infer_schema_document() {
+        // [{"id": 1, "tags": ["a"], "score": 2},
+        //  {"id": 300, "tags": [], "score": 2.5, "note": null},
+        //  {"id": 2, "tags": null, "note": "x"}]
+        let bytes = b"\xcb\x4a\
+            \xcc\x15\x27id\x131\x47tags\x2b\x17a\x57score\x132\
+            \xcc\x1d\x27id\x33300\x47tags\x0b\x57score\x352.5\x47note\x00\
+            \xcc\x12\x27id\x132\x47tags\x00\x47note\x17x";
+        assert_eq!(
+            infer_schema(bytes).unwrap(),
+            Schema::Array(Box::new(Schema::Object(vec![
+                ("id".into(), Schema::Int(IntWidth::U16)),
+                (
+                    "tags".into(),
+                    optional(Schema::Array(Box::new(Schema::String)))
+                ),
+                ("score".into(), optional(Schema::Float)),
+                ("note".into(), optional(Schema::String)),
+            ])))
+        );
+    }
+
+    #[test]
+    fn test_infer_schema_mixed() {
+        // [1, "a", null]
+        assert_eq!(
+            infer_schema(b"\x5b\x131\x17a\x00").unwrap(),
+            Schema::Array(Box::new(optional(Schema::Mixed)))
+        );
+        // {"a"}
+        assert_eq!(infer_schema(b"\x2c\x17a"), Err(Error::MissingObjectValue));
+    }
+}
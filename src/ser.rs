@@ -1,13 +1,78 @@
 use crate::{
+    bytes::BytesEncoding,
     error::{Error, Result},
-    header::ElementType,
+    header::{ElementType, Header},
+    raw::RawElement,
 };
 use serde::ser::{self, Serialize};
 use std::io::Write;
 
-#[derive(Debug, Default)]
+/// A structure that serializes Rust values into SQLite JSONB data.
+#[derive(Debug)]
 pub struct Serializer {
     buffer: Vec<u8>,
+    human_readable: bool,
+    bytes_encoding: BytesEncoding,
+    sorted_keys: bool,
+    /// Whether the next byte string is the bytes of a [`JsonbRawValue`],
+    /// to be written as they are.
+    ///
+    /// [`JsonbRawValue`]: crate::JsonbRawValue
+    raw_value: bool,
+    /// Whether the next string is the text of a number, written by
+    /// [`number_text`](crate::number_text).
+    number_text: bool,
+}
+
+impl Default for Serializer {
+    fn default() -> Self {
+        Serializer {
+            buffer: Vec::new(),
+            human_readable: true,
+            bytes_encoding: BytesEncoding::default(),
+            sorted_keys: false,
+            raw_value: false,
+            number_text: false,
+        }
+    }
+}
+
+impl Serializer {
+    /// Create a serializer that writes into a new buffer.
+    pub fn new() -> Self {
+        Serializer::default()
+    }
+
+    /// Set the value returned by `is_human_readable`, which changes how some
+    /// types (such as IP addresses or UUIDs) choose to serialize themselves.
+    ///
+    /// Defaults to `true`, for compatibility with types serialized to JSON.
+    pub fn with_human_readable(mut self, human_readable: bool) -> Self {
+        self.human_readable = human_readable;
+        self
+    }
+
+    /// Choose how byte strings are written. Defaults to an array of
+    /// integers.
+    pub fn with_bytes_encoding(mut self, encoding: BytesEncoding) -> Self {
+        self.bytes_encoding = encoding;
+        self
+    }
+
+    /// Write the entries of maps and structs sorted by the bytes of their
+    /// keys, instead of in the order they are serialized in, for output that
+    /// does not depend on the iteration order of a `HashMap`.
+    ///
+    /// Entries with the same key keep their relative order.
+    pub fn with_sorted_keys(mut self, sorted: bool) -> Self {
+        self.sorted_keys = sorted;
+        self
+    }
+
+    /// Return the JSONB data written so far.
+    pub fn into_inner(self) -> Vec<u8> {
+        self.buffer
+    }
 }
 
 /// Serialize a value into a JSONB byte array
@@ -22,52 +87,78 @@ where
 
 /// Helper struct to write JSONB data, then finalize the header to its minimal size
 pub struct JsonbWriter<'a> {
-    buffer: &'a mut Vec<u8>,
+    ser: &'a mut Serializer,
     header_start: usize,
 }
 
 impl<'a> JsonbWriter<'a> {
-    fn new(buffer: &'a mut Vec<u8>, element_type: ElementType) -> Self {
-        let header_start = buffer.len();
-        buffer.extend_from_slice(&[u8::from(element_type); 9]);
-        Self {
-            buffer,
-            header_start,
-        }
+    fn new(ser: &'a mut Serializer, element_type: ElementType) -> Self {
+        let header_start = ser.buffer.len();
+        ser.buffer.extend_from_slice(&[u8::from(element_type); 9]);
+        Self { ser, header_start }
     }
+    /// Write the name of an enum variant as an object key, with the same
+    /// element type as the keys of maps and structs.
+    fn write_variant_key(&mut self, variant: &str) -> Result<()> {
+        self.ser.write_displayable(ElementType::TextRaw, variant)
+    }
+
     fn finalize(self) {
-        let data_start = self.header_start + 9;
-        let data_end = self.buffer.len();
-        let payload_size = data_end - data_start;
-        let header = &mut self.buffer[self.header_start..self.header_start + 9];
-        let head_len = if payload_size <= 11 {
-            header[0] |= (payload_size as u8) << 4;
-            1
-        } else if payload_size <= 0xff {
-            header[0] |= 0xc0;
-            header[1] = payload_size as u8;
-            2
-        } else if payload_size <= 0xffff {
-            header[0] |= 0xd0;
-            header[1..3].copy_from_slice(&(payload_size as u16).to_be_bytes());
-            3
-        } else if payload_size <= 0xffffffff {
-            header[0] |= 0xe0;
-            header[1..5].copy_from_slice(&(payload_size as u32).to_be_bytes());
-            5
-        } else {
-            header[0] |= 0xf0;
-            header[1..9].copy_from_slice(&payload_size.to_be_bytes());
-            9
-        };
-        if head_len < 9 {
-            self.buffer.copy_within(
-                data_start..data_end,
-                self.header_start + head_len,
-            );
-            self.buffer
-                .truncate(self.header_start + head_len + payload_size);
+        finalize_header(&mut self.ser.buffer, self.header_start);
+    }
+
+    /// Finalize an object, sorting its entries first if needed.
+    fn finalize_object(self) -> Result<()> {
+        if self.ser.sorted_keys {
+            sort_object_entries(&mut self.ser.buffer, self.header_start + 9)?;
         }
+        self.finalize();
+        Ok(())
+    }
+}
+
+/// Sort the key-value pairs that span `buffer` from `payload_start` to its
+/// end by the payload of their keys.
+fn sort_object_entries(
+    buffer: &mut Vec<u8>,
+    payload_start: usize,
+) -> Result<()> {
+    let payload = &buffer[payload_start..];
+    let mut rest = payload;
+    let mut entries = Vec::new();
+    while !rest.is_empty() {
+        let start = payload.len() - rest.len();
+        let key = RawElement::read(&mut rest)?;
+        RawElement::read(&mut rest)?;
+        entries.push((key.payload(), start..payload.len() - rest.len()));
+    }
+    // a stable sort, that keeps duplicate keys in order
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    let mut sorted = Vec::with_capacity(payload.len());
+    for (_, range) in entries {
+        sorted.extend_from_slice(&payload[range]);
+    }
+    buffer.truncate(payload_start);
+    buffer.extend_from_slice(&sorted);
+    Ok(())
+}
+
+/// Replace the nine-byte placeholder header at `header_start` with the
+/// smallest header for the payload that follows it, up to the end of
+/// `buffer`. The placeholder only needs to contain the element type.
+pub(crate) fn finalize_header(buffer: &mut Vec<u8>, header_start: usize) {
+    let data_start = header_start + 9;
+    let data_end = buffer.len();
+    let payload_size = data_end - data_start;
+    let header = Header {
+        element_type: ElementType::from_header_byte(buffer[header_start]),
+        payload_size,
+    };
+    let (head, head_len) = header.to_minimal_bytes();
+    buffer[header_start..data_start].copy_from_slice(&head);
+    if head_len < 9 {
+        buffer.copy_within(data_start..data_end, header_start + head_len);
+        buffer.truncate(header_start + head_len + payload_size);
     }
 }
 
@@ -77,18 +168,40 @@ impl Serializer {
         Ok(())
     }
 
+    /// Write a string using the cheapest text element type for it.
+    fn write_text(&mut self, v: &str) -> Result<()> {
+        self.write_displayable(text_element_type(v), v)
+    }
+
     fn write_displayable(
         &mut self,
         element_type: ElementType,
         data: impl std::fmt::Display,
     ) -> Result<()> {
-        let mut w = JsonbWriter::new(&mut self.buffer, element_type);
-        write!(&mut w.buffer, "{}", data)?;
+        let w = JsonbWriter::new(self, element_type);
+        write!(&mut w.ser.buffer, "{}", data)?;
         w.finalize();
         Ok(())
     }
 }
 
+/// The element type to store a string in without escaping it: the plain
+/// `Text` type when its content can be copied verbatim into JSON, and
+/// `TextRaw` otherwise.
+pub(crate) fn text_element_type(s: &str) -> ElementType {
+    if needs_escaping(s) {
+        ElementType::TextRaw
+    } else {
+        ElementType::Text
+    }
+}
+
+/// Whether a string contains characters that would need to be escaped
+/// in a JSON string literal.
+fn needs_escaping(s: &str) -> bool {
+    s.bytes().any(|b| b == b'"' || b == b'\\' || b < 0x20)
+}
+
 impl<'a> ser::Serializer for &'a mut Serializer {
     type Ok = ();
 
@@ -108,6 +221,10 @@ impl<'a> ser::Serializer for &'a mut Serializer {
 
     type SerializeStructVariant = EnumVariantSerializer<'a>;
 
+    fn is_human_readable(&self) -> bool {
+        self.human_readable
+    }
+
     fn serialize_bool(self, v: bool) -> Result<Self::Ok> {
         self.write_header_nodata(if v {
             ElementType::True
@@ -132,6 +249,10 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         self.write_displayable(ElementType::Int, v)
     }
 
+    fn serialize_i128(self, v: i128) -> Result<Self::Ok> {
+        self.write_displayable(ElementType::Int, v)
+    }
+
     fn serialize_u8(self, v: u8) -> Result<Self::Ok> {
         self.write_displayable(ElementType::Int, v)
     }
@@ -148,7 +269,13 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         self.write_displayable(ElementType::Int, v)
     }
 
+    fn serialize_u128(self, v: u128) -> Result<Self::Ok> {
+        self.write_displayable(ElementType::Int, v)
+    }
+
     fn serialize_f32(self, v: f32) -> Result<Self::Ok> {
+        // the standard library prints the shortest decimal that parses back
+        // to the same f32, without going through f64
         self.write_displayable(ElementType::Float, v)
     }
 
@@ -157,15 +284,58 @@ impl<'a> ser::Serializer for &'a mut Serializer {
     }
 
     fn serialize_char(self, v: char) -> Result<Self::Ok> {
-        self.write_displayable(ElementType::TextRaw, v)
+        let mut buf = [0u8; 4];
+        let s = v.encode_utf8(&mut buf);
+        if needs_escaping(s) {
+            let mut escaped = String::new();
+            crate::printer::write_json_escaped(s, &mut escaped);
+            self.write_displayable(ElementType::TextJ, escaped)
+        } else {
+            self.write_displayable(ElementType::Text, s)
+        }
     }
 
     fn serialize_str(self, v: &str) -> Result<Self::Ok> {
+        if std::mem::take(&mut self.number_text) {
+            if !crate::number_text::is_json_number(v) {
+                return Err(Error::Message(format!("{v:?} is not a number")));
+            }
+            let integer = !v.contains(['.', 'e', 'E']);
+            let t = if integer {
+                ElementType::Int
+            } else {
+                ElementType::Float
+            };
+            return self.write_displayable(t, v);
+        }
         self.write_displayable(ElementType::TextRaw, v)
     }
 
+    /// Format the value directly into the output, instead of building an
+    /// intermediate `String` like the default implementation does.
+    fn collect_str<T>(self, value: &T) -> Result<Self::Ok>
+    where
+        T: ?Sized + std::fmt::Display,
+    {
+        self.write_displayable(ElementType::TextRaw, value)
+    }
+
     fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok> {
         use serde::ser::SerializeSeq;
+        if std::mem::take(&mut self.raw_value) {
+            self.buffer.extend_from_slice(v);
+            return Ok(());
+        }
+        match self.bytes_encoding {
+            BytesEncoding::Array => {}
+            #[cfg(feature = "base64")]
+            BytesEncoding::Base64 => {
+                let w = JsonbWriter::new(self, ElementType::Text);
+                crate::base64::encode_into(v, &mut w.ser.buffer);
+                w.finalize();
+                return Ok(());
+            }
+        }
         let mut s = self.serialize_seq(Some(v.len()))?;
         for byte in v {
             s.serialize_element(byte)?;
@@ -198,14 +368,22 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         _variant_index: u32,
         variant: &'static str,
     ) -> Result<Self::Ok> {
-        self.serialize_str(variant)
+        self.write_text(variant)
     }
 
     fn serialize_newtype_struct<T: ?Sized + Serialize>(
         self,
-        _name: &'static str,
-        _value: &T,
+        name: &'static str,
+        value: &T,
     ) -> Result<Self::Ok> {
+        if name == crate::raw_value::TOKEN {
+            self.raw_value = true;
+            return value.serialize(self);
+        }
+        if name == crate::number_text::TOKEN {
+            self.number_text = true;
+            return value.serialize(self);
+        }
         self.serialize_unit()
     }
 
@@ -216,18 +394,18 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         variant: &'static str,
         value: &T,
     ) -> Result<Self::Ok> {
-        let mut map = self.serialize_map(Some(1))?;
-        serde::ser::SerializeMap::serialize_key(&mut map, variant)?;
+        let mut map = JsonbWriter::new(self, ElementType::Object);
+        map.write_variant_key(variant)?;
         serde::ser::SerializeMap::serialize_value(&mut map, value)?;
         serde::ser::SerializeMap::end(map)
     }
 
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq> {
-        Ok(JsonbWriter::new(&mut self.buffer, ElementType::Array))
+        Ok(JsonbWriter::new(self, ElementType::Array))
     }
 
     fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple> {
-        Ok(JsonbWriter::new(&mut self.buffer, ElementType::Array))
+        Ok(JsonbWriter::new(self, ElementType::Array))
     }
 
     fn serialize_tuple_struct(
@@ -245,15 +423,11 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant> {
-        Ok(EnumVariantSerializer::new(
-            &mut self.buffer,
-            variant,
-            ElementType::Array,
-        ))
+        EnumVariantSerializer::new(self, variant, ElementType::Array)
     }
 
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
-        Ok(JsonbWriter::new(&mut self.buffer, ElementType::Object))
+        Ok(JsonbWriter::new(self, ElementType::Object))
     }
 
     fn serialize_struct(
@@ -268,14 +442,10 @@ impl<'a> ser::Serializer for &'a mut Serializer {
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant> {
-        Ok(EnumVariantSerializer::new(
-            &mut self.buffer,
-            _variant,
-            ElementType::Object,
-        ))
+        EnumVariantSerializer::new(self, variant, ElementType::Object)
     }
 }
 
@@ -288,11 +458,7 @@ impl<'a> ser::SerializeSeq for JsonbWriter<'a> {
         &mut self,
         value: &T,
     ) -> Result<()> {
-        let mut serializer = Serializer::default();
-        std::mem::swap(self.buffer, &mut serializer.buffer);
-        let r = value.serialize(&mut serializer);
-        std::mem::swap(self.buffer, &mut serializer.buffer);
-        r
+        value.serialize(&mut *self.ser)
     }
 
     fn end(self) -> Result<Self::Ok> {
@@ -347,20 +513,18 @@ pub struct EnumVariantSerializer<'a> {
 
 impl<'a> EnumVariantSerializer<'a> {
     fn new(
-        buffer: &'a mut Vec<u8>,
+        ser: &'a mut Serializer,
         variant: &'static str,
         inner_element_type: ElementType,
-    ) -> Self {
-        let mut map_jsonb_writer =
-            JsonbWriter::new(buffer, ElementType::Object);
-        ser::SerializeMap::serialize_key(&mut map_jsonb_writer, variant)
-            .unwrap();
+    ) -> Result<Self> {
+        let mut map_jsonb_writer = JsonbWriter::new(ser, ElementType::Object);
+        map_jsonb_writer.write_variant_key(variant)?;
         let map_header_start = map_jsonb_writer.header_start;
-        let inner_jsonb_writer = JsonbWriter::new(buffer, inner_element_type);
-        Self {
+        let inner_jsonb_writer = JsonbWriter::new(ser, inner_element_type);
+        Ok(Self {
             map_header_start,
             inner_jsonb_writer,
-        }
+        })
     }
 }
 
@@ -381,11 +545,11 @@ impl<'a> ser::SerializeTupleVariant for EnumVariantSerializer<'a> {
 
     fn end(self) -> Result<Self::Ok> {
         ser::SerializeSeq::end(JsonbWriter {
-            buffer: self.inner_jsonb_writer.buffer,
+            ser: self.inner_jsonb_writer.ser,
             header_start: self.inner_jsonb_writer.header_start,
         })?;
         ser::SerializeMap::end(JsonbWriter {
-            buffer: self.inner_jsonb_writer.buffer,
+            ser: self.inner_jsonb_writer.ser,
             header_start: self.map_header_start,
         })
     }
@@ -408,8 +572,7 @@ impl<'a> ser::SerializeMap for JsonbWriter<'a> {
     }
 
     fn end(self) -> Result<Self::Ok> {
-        self.finalize();
-        Ok(())
+        self.finalize_object()
     }
 }
 
@@ -428,8 +591,7 @@ impl<'a> ser::SerializeStruct for JsonbWriter<'a> {
     }
 
     fn end(self) -> Result<Self::Ok> {
-        self.finalize();
-        Ok(())
+        self.finalize_object()
     }
 }
 
@@ -447,7 +609,11 @@ impl<'a> ser::SerializeStructVariant for EnumVariantSerializer<'a> {
         ser::SerializeTupleVariant::serialize_field(self, value)
     }
 
-    fn end(self) -> Result<Self::Ok> {
+    fn end(mut self) -> Result<Self::Ok> {
+        let inner = &mut self.inner_jsonb_writer;
+        if inner.ser.sorted_keys {
+            sort_object_entries(&mut inner.ser.buffer, inner.header_start + 9)?;
+        }
         ser::SerializeTupleVariant::end(self)
     }
 }
@@ -469,6 +635,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_serialize_f32() {
+        // no digits are added by a conversion to f64
+        assert_eq!(to_vec(&0.1f32).unwrap(), b"\x350.1");
+        assert_eq!(to_vec(&16777216f32).unwrap(), b"\x8516777216");
+    }
+
+    #[test]
+    fn test_f32_roundtrip() {
+        let special = [
+            0.1,
+            -0.0,
+            f32::MIN_POSITIVE,
+            f32::MAX,
+            f32::MIN,
+            f32::EPSILON,
+            f32::from_bits(1),
+            f32::from_bits(0x007f_ffff),
+        ];
+        let sampled = (0..0x7f80_0000u32)
+            .step_by(0x1_0001)
+            .map(f32::from_bits)
+            .flat_map(|f| [f, -f]);
+        for f in special.into_iter().chain(sampled) {
+            let encoded = to_vec(&f).unwrap();
+            assert_eq!(encoded[0] & 0x0f, u8::from(ElementType::Float));
+            let decoded: f32 = crate::from_slice(&encoded).unwrap();
+            assert_eq!(decoded.to_bits(), f.to_bits(), "{f}");
+        }
+    }
+
     #[test]
     fn test_serialize_bool() {
         assert_eq!(to_vec(&true).unwrap(), b"\x01");
@@ -484,7 +681,7 @@ mod tests {
         let long_str = "x".repeat(repeats);
         assert_eq!(
             to_vec(&long_str).unwrap(),
-            [&expected_header[..], &long_str.as_bytes()].concat()
+            [expected_header, long_str.as_bytes()].concat()
         );
     }
 
@@ -502,6 +699,26 @@ mod tests {
         // assert_long_str(0x01_0000_0000, b"\xfa\x00\x00\x00\x01\x00\x00\x00\x00");
     }
 
+    #[test]
+    fn test_serialize_collect_str() {
+        struct Point(i32, i32);
+        impl Serialize for Point {
+            fn serialize<S: ser::Serializer>(
+                &self,
+                serializer: S,
+            ) -> std::result::Result<S::Ok, S::Error> {
+                serializer
+                    .collect_str(&format_args!("({}, {})", self.0, self.1))
+            }
+        }
+        let encoded = to_vec(&[Point(1, -2)]).unwrap();
+        assert_eq!(encoded, b"\x8b\x7a(1, -2)");
+        assert_eq!(
+            crate::from_slice::<Vec<String>>(&encoded).unwrap(),
+            ["(1, -2)"]
+        );
+    }
+
     #[test]
     fn test_serialize_array() {
         assert_eq!(
@@ -523,8 +740,8 @@ mod tests {
         struct TupleStruct(String, f32);
 
         assert_eq!(
-            to_vec(&TupleStruct("hello".to_string(), 3.14)).unwrap(),
-            b"\xbb\x5ahello\x453.14"
+            to_vec(&TupleStruct("hello".to_string(), 1.25)).unwrap(),
+            b"\xbb\x5ahello\x451.25"
         );
     }
 
@@ -541,7 +758,7 @@ mod tests {
         };
         assert_eq!(
             to_vec(&test_struct).unwrap(),
-            b"\xcc\x1f\x4asmol\x1aX\xca\x13long_long_long_long\x2342"
+            b"\xcc\x1f\x4asmol\x17X\xca\x13long_long_long_long\x2342"
         );
     }
 
@@ -552,6 +769,52 @@ mod tests {
         assert_eq!(to_vec(&test_map).unwrap(), b"\x3c\x1ak\x02",);
     }
 
+    #[test]
+    fn test_serialize_sorted_keys() {
+        use std::collections::HashMap;
+        let map: HashMap<String, u8> =
+            (0..20).map(|i| (format!("key{i:02}"), i)).collect();
+        let mut ser = Serializer::new().with_sorted_keys(true);
+        map.serialize(&mut ser).unwrap();
+        let sorted = ser.into_inner();
+        let btree: std::collections::BTreeMap<_, _> = map.iter().collect();
+        assert_eq!(sorted, to_vec(&btree).unwrap());
+        // without sorting, the entries are in the iteration order of the map
+        let unsorted = to_vec(&map).unwrap();
+        let keys: Vec<String> =
+            crate::from_slice::<Vec<(String, u8)>>(&unsorted)
+                .unwrap()
+                .into_iter()
+                .map(|(k, _)| k)
+                .collect();
+        let expected: Vec<&String> = map.keys().collect();
+        assert_eq!(keys.iter().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn test_serialize_sorted_struct_fields() {
+        #[derive(serde_derive::Serialize)]
+        enum E {
+            V { b: u8, a: u8 },
+        }
+        #[derive(serde_derive::Serialize)]
+        struct S {
+            z: bool,
+            y: E,
+        }
+        let value = S {
+            z: true,
+            y: E::V { b: 1, a: 2 },
+        };
+        let mut ser = Serializer::new().with_sorted_keys(true);
+        value.serialize(&mut ser).unwrap();
+        // {"y": {"V": {"a": 2, "b": 1}}, "z": true}
+        assert_eq!(
+            ser.into_inner(),
+            b"\xcc\x11\x1ay\xbc\x1aV\x8c\x1aa\x132\x1ab\x131\x1az\x01"
+        );
+    }
+
     #[test]
     fn test_serialize_empty_map() {
         let test_map = std::collections::HashMap::<String, ()>::new();
@@ -585,8 +848,8 @@ mod tests {
             B,
         }
 
-        assert_eq!(to_vec(&Enum::A).unwrap(), b"\x1aA");
-        assert_eq!(to_vec(&Enum::B).unwrap(), b"\x1aB");
+        assert_eq!(to_vec(&Enum::A).unwrap(), b"\x17A");
+        assert_eq!(to_vec(&Enum::B).unwrap(), b"\x17B");
     }
 
     #[test]
@@ -618,4 +881,175 @@ mod tests {
         let test_struct = E::S { x: true };
         assert_eq!(to_vec(&test_struct).unwrap(), b"\x6c\x1aS\x3c\x1ax\x01");
     }
+
+    #[derive(
+        Debug, PartialEq, serde_derive::Serialize, serde_derive::Deserialize,
+    )]
+    enum Shape {
+        Empty,
+        Circle(f64),
+        Rectangle { width: u32, height: u32 },
+    }
+
+    fn assert_roundtrip(shape: Shape) {
+        let encoded = to_vec(&shape).unwrap();
+        let decoded: Shape = crate::from_slice(&encoded).unwrap();
+        assert_eq!(decoded, shape, "{encoded:x?}");
+    }
+
+    #[test]
+    fn test_roundtrip_enum_unit_variant() {
+        assert_eq!(to_vec(&Shape::Empty).unwrap(), b"\x57Empty");
+        assert_roundtrip(Shape::Empty);
+    }
+
+    #[test]
+    fn test_roundtrip_enum_newtype_variant() {
+        assert_roundtrip(Shape::Circle(1.5));
+    }
+
+    #[test]
+    fn test_roundtrip_enum_struct_variant() {
+        assert_roundtrip(Shape::Rectangle {
+            width: 3,
+            height: 400,
+        });
+    }
+
+    #[test]
+    fn test_serialize_enum_variant_needing_escapes() {
+        #[derive(serde_derive::Serialize)]
+        enum Enum {
+            #[serde(rename = "a\"b")]
+            A,
+        }
+        assert_eq!(to_vec(&Enum::A).unwrap(), b"\x3aa\"b");
+    }
+
+    #[test]
+    fn test_human_readable() {
+        #[derive(
+            Debug, PartialEq, serde_derive::Serialize, serde_derive::Deserialize,
+        )]
+        struct Row {
+            id: uuid::Uuid,
+        }
+        let row = Row {
+            id: uuid::Uuid::from_u128(
+                0x0123_4567_89ab_cdef_0123_4567_89ab_cdef,
+            ),
+        };
+        for human_readable in [true, false] {
+            let mut ser = Serializer::new().with_human_readable(human_readable);
+            row.serialize(&mut ser).unwrap();
+            let encoded = ser.into_inner();
+            let mut de = crate::Deserializer::from_bytes(&encoded)
+                .with_human_readable(human_readable);
+            let decoded = <Row as serde::Deserialize>::deserialize(&mut de);
+            assert_eq!(decoded.unwrap(), row);
+            let expected_id_type = if human_readable {
+                ElementType::TextRaw
+            } else {
+                ElementType::Array
+            };
+            let id_header = encoded[5];
+            assert_eq!(
+                ElementType::from_header_byte(id_header),
+                expected_id_type
+            );
+        }
+        assert_eq!(
+            crate::from_slice::<uuid::Uuid>(
+                b"\xca\x2401234567-89ab-cdef-0123-456789abcdef"
+            )
+            .unwrap(),
+            row.id
+        );
+    }
+
+    #[test]
+    fn test_serialize_bytes() {
+        #[derive(
+            Debug, PartialEq, serde_derive::Serialize, serde_derive::Deserialize,
+        )]
+        struct Blob {
+            #[serde(with = "serde_bytes")]
+            data: Vec<u8>,
+        }
+        let blob = Blob {
+            data: vec![0, 159, 146, 150, 255],
+        };
+        // the default options on both sides roundtrip
+        let encoded = to_vec(&blob).unwrap();
+        assert_eq!(
+            encoded,
+            b"\xcc\x19\x4adata\xcb\x12\x130\x33159\x33146\x33150\x33255"
+        );
+        assert_eq!(crate::from_slice::<Blob>(&encoded).unwrap(), blob);
+    }
+
+    #[test]
+    #[cfg(feature = "base64")]
+    fn test_serialize_bytes_base64() {
+        #[derive(
+            Debug, PartialEq, serde_derive::Serialize, serde_derive::Deserialize,
+        )]
+        struct Blob {
+            #[serde(with = "serde_bytes")]
+            data: Vec<u8>,
+        }
+        let blob = Blob {
+            data: vec![0, 159, 146, 150, 255],
+        };
+        let mut ser =
+            Serializer::new().with_bytes_encoding(BytesEncoding::Base64);
+        blob.serialize(&mut ser).unwrap();
+        let encoded = ser.into_inner();
+        assert_eq!(encoded, b"\xcc\x0e\x4adata\x87AJ+Slv8=");
+        let mut de =
+            crate::Deserializer::from_bytes(&encoded).with_base64_bytes(true);
+        assert_eq!(
+            <Blob as serde::Deserialize>::deserialize(&mut de).unwrap(),
+            blob
+        );
+        let mut de = crate::Deserializer::from_bytes(b"\x4aZg=a")
+            .with_base64_bytes(true);
+        assert!(<serde_bytes::ByteBuf as serde::Deserialize>::deserialize(
+            &mut de
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_char_roundtrip() {
+        for (c, encoded) in [
+            ('a', &b"\x17a"[..]),
+            ('é', b"\x27\xc3\xa9"),
+            ('😀', b"\x47\xf0\x9f\x98\x80"),
+            ('"', b"\x28\\\""),
+            ('\n', b"\x28\\n"),
+        ] {
+            assert_eq!(to_vec(&c).unwrap(), encoded, "encoding {c:?}");
+            assert_eq!(crate::from_slice::<char>(encoded).unwrap(), c);
+        }
+    }
+
+    #[test]
+    fn test_serialize_128_bit_integers() {
+        assert_eq!(to_vec(&-1i128).unwrap(), b"\x23-1");
+        assert_eq!(to_vec(&42u128).unwrap(), b"\x2342");
+    }
+
+    #[test]
+    fn test_roundtrip_128_bit_integers() {
+        for v in [i128::MIN, i128::from(i64::MIN) - 1, -1, 0, i128::MAX] {
+            let encoded = to_vec(&v).unwrap();
+            assert_eq!(crate::root_type(&encoded).unwrap(), ElementType::Int);
+            assert_eq!(crate::from_slice::<i128>(&encoded).unwrap(), v);
+        }
+        for v in [0, u128::from(u64::MAX) + 1, u128::MAX] {
+            let encoded = to_vec(&v).unwrap();
+            assert_eq!(crate::from_slice::<u128>(&encoded).unwrap(), v);
+        }
+    }
 }
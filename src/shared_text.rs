@@ -0,0 +1,144 @@
+//! Decoding of text into [`Bytes`] that share the input buffer, for use with
+//! `#[serde(with = "...")]` on fields of services built on the `bytes`
+//! crate.
+//!
+//! When the input is decoded with [`from_bytes_buf`](crate::from_bytes_buf),
+//! the payload of a `Text` or `TextRaw` element is handed out as a slice of
+//! the input buffer, without copying it. Text that needs unescaping, or that
+//! is decoded from other inputs, is copied into a new buffer.
+//!
+//! ```
+//! use bytes::Bytes;
+//!
+//! #[derive(serde::Deserialize)]
+//! struct Message {
+//!     #[serde(with = "serde_sqlite_jsonb::shared_text")]
+//!     body: Bytes,
+//! }
+//!
+//! // {"body": "hi"}
+//! let buf = Bytes::from_static(b"\x8c\x47body\x27hi");
+//! let message: Message = serde_sqlite_jsonb::from_bytes_buf(buf).unwrap();
+//! assert_eq!(message.body, "hi");
+//! ```
+//!
+//! Values are serialized as text, and must be valid UTF-8.
+
+use ::bytes::Bytes;
+use serde::de::{self, Deserializer, Visitor};
+use serde::ser::{self, Serializer};
+use std::cell::Cell;
+use std::fmt;
+
+/// The name of the newtype struct that text is deserialized as, which the
+/// JSONB [`Deserializer`](crate::Deserializer) recognizes.
+pub(crate) const TOKEN: &str = "$serde_sqlite_jsonb::private::SharedText";
+
+thread_local! {
+    /// The slice of the input handed from the deserializer to the visitor,
+    /// which cannot receive it through the serde API.
+    static HANDED_OVER: Cell<Option<Bytes>> = const { Cell::new(None) };
+}
+
+/// Make `text` the value of the next `visit_unit` of the visitor.
+pub(crate) fn hand_over(text: Bytes) {
+    HANDED_OVER.with(|handed_over| handed_over.set(Some(text)));
+}
+
+/// Serialize bytes as a text element.
+pub fn serialize<S>(value: &Bytes, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let text = std::str::from_utf8(value).map_err(ser::Error::custom)?;
+    serializer.serialize_str(text)
+}
+
+/// Deserialize the text of a text element into bytes, sharing the input
+/// buffer when possible.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Bytes, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    deserializer.deserialize_newtype_struct(TOKEN, SharedTextVisitor)
+}
+
+struct SharedTextVisitor;
+
+impl<'de> Visitor<'de> for SharedTextVisitor {
+    type Value = Bytes;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a string")
+    }
+
+    // the JSONB deserializer hands over a slice of its input
+    fn visit_unit<E: de::Error>(self) -> Result<Bytes, E> {
+        HANDED_OVER
+            .with(Cell::take)
+            .ok_or_else(|| E::invalid_type(de::Unexpected::Unit, &self))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Bytes, E> {
+        Ok(Bytes::copy_from_slice(v.as_bytes()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Bytes, E> {
+        Ok(Bytes::from(v))
+    }
+
+    // other formats do not recognize the newtype struct
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Bytes, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_string(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Error;
+
+    #[derive(
+        Debug, PartialEq, serde_derive::Serialize, serde_derive::Deserialize,
+    )]
+    struct User {
+        id: u8,
+        #[serde(with = "super")]
+        name: Bytes,
+    }
+
+    #[test]
+    fn test_shared_text_shares_buffer() {
+        let user = User {
+            id: 1,
+            name: Bytes::from_static(b"alice"),
+        };
+        let buf = Bytes::from(crate::to_vec(&user).unwrap());
+        let decoded: User = crate::from_bytes_buf(buf.clone()).unwrap();
+        assert_eq!(decoded, user);
+        let range = buf.as_ptr_range();
+        assert!(range.contains(&decoded.name.as_ptr()));
+
+        // without the buffer, the text is copied
+        let decoded: User = crate::from_slice(&buf).unwrap();
+        assert_eq!(decoded, user);
+        assert!(!range.contains(&decoded.name.as_ptr()));
+    }
+
+    #[test]
+    fn test_shared_text_escaped_and_invalid() {
+        // {"id": 2, "name": "a\nb"}, with the name in a TextJ element
+        let buf = Bytes::from_static(b"\xcc\x0f\x27id\x132\x47name\x48a\\nb");
+        let decoded: User = crate::from_bytes_buf(buf).unwrap();
+        assert_eq!(decoded.name, "a\nb");
+        // {"id": 2, "name": 3}
+        let buf = Bytes::from_static(b"\xcc\x0c\x27id\x132\x47name\x133");
+        assert!(matches!(
+            crate::from_bytes_buf::<User>(buf),
+            Err(Error::UnexpectedType { .. })
+        ));
+    }
+}
@@ -0,0 +1,106 @@
+//! Statistics about the structure of JSONB data, computed from its headers.
+
+use crate::error::Result;
+use crate::header::ElementType;
+use crate::raw::walk_checked;
+
+/// Statistics about the structure of a JSONB value.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// Number of elements of each type, indexed by element type code.
+    pub element_counts: [usize; 16],
+    /// Maximum number of nested arrays and objects.
+    /// A scalar has a depth of 0, and `[[1]]` has a depth of 2.
+    pub max_depth: usize,
+    /// Total number of elements, containers included.
+    pub total_elements: usize,
+}
+
+impl Summary {
+    /// Number of elements of the given type.
+    pub fn count(&self, element_type: ElementType) -> usize {
+        self.element_counts[usize::from(u8::from(element_type))]
+    }
+}
+
+/// Compute statistics about a JSONB value in a single pass over its headers,
+/// without decoding any payload.
+///
+/// Elements of the reserved types 13 to 15 are counted like the others, and
+/// their payload is skipped.
+pub fn summarize(bytes: &[u8]) -> Result<Summary> {
+    let mut summary = Summary::default();
+    walk_checked(bytes, true, |element, depth| {
+        let element_type = element.element_type();
+        summary.element_counts[usize::from(u8::from(element_type))] += 1;
+        summary.total_elements += 1;
+        let depth = depth + usize::from(element.is_container());
+        summary.max_depth = summary.max_depth.max(depth);
+        Ok(())
+    })?;
+    Ok(summary)
+}
+
+/// Count the elements of each type in a JSONB value, indexed by element
+/// type code, in a single pass over its headers.
+///
+/// Unlike [`ElementType`], the indices tell apart the variants of each kind
+/// of element, such as `Int` and `Int5`, or `Text` and `TextJ`. Elements of
+/// reserved types are counted at indices 13 to 15, as with [`summarize`].
+pub fn element_type_histogram(bytes: &[u8]) -> Result<[usize; 16]> {
+    Ok(summarize(bytes)?.element_counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summarize_nested() {
+        // {"a": [1, 2.5, null], "b": {"c": "x"}}
+        let bytes = b"\xcc\x11\x17a\x7b\x131\x352.5\x00\x17b\x4c\x17c\x17x";
+        let summary = summarize(bytes).unwrap();
+        assert_eq!(summary.count(ElementType::Object), 2);
+        assert_eq!(summary.count(ElementType::Array), 1);
+        assert_eq!(summary.count(ElementType::Text), 4);
+        assert_eq!(summary.count(ElementType::Int), 1);
+        assert_eq!(summary.count(ElementType::Float), 1);
+        assert_eq!(summary.count(ElementType::Null), 1);
+        assert_eq!(summary.count(ElementType::TextJ), 0);
+        assert_eq!(summary.total_elements, 10);
+        assert_eq!(summary.max_depth, 2);
+    }
+
+    #[test]
+    fn test_summarize_scalar() {
+        let summary = summarize(b"\x131").unwrap();
+        assert_eq!(summary.total_elements, 1);
+        assert_eq!(summary.max_depth, 0);
+    }
+
+    #[test]
+    fn test_element_type_histogram() {
+        // [1, 0x1F, "a", "b\n", "c", {"d": null}, 1.5]
+        let bytes =
+            b"\xcb\x17\x131\x440x1F\x17a\x38b\\n\x17c\x3c\x17d\x00\x351.5";
+        let mut expected = [0; 16];
+        expected[0x0] = 1; // Null
+        expected[0x3] = 1; // Int
+        expected[0x4] = 1; // Int5
+        expected[0x5] = 1; // Float
+        expected[0x7] = 3; // Text
+        expected[0x8] = 1; // TextJ
+        expected[0xb] = 1; // Array
+        expected[0xc] = 1; // Object
+        assert_eq!(element_type_histogram(bytes).unwrap(), expected);
+        // [<reserved 13>, "a", <reserved 15 with a payload>]
+        let bytes = b"\x6b\x0d\x17a\x2fxy";
+        let mut expected = [0; 16];
+        expected[0x7] = 1; // Text
+        expected[0xb] = 1; // Array
+        expected[0xd] = 1; // Reserved13
+        expected[0xf] = 1; // Reserved15
+        assert_eq!(element_type_histogram(bytes).unwrap(), expected);
+        assert_eq!(element_type_histogram(b"\x1ex").unwrap()[0xe], 1);
+    }
+}
@@ -0,0 +1,328 @@
+//! Best-effort validation that reports every problem found in a value.
+
+use crate::de::OnReserved;
+use crate::error::{Error, Result};
+use crate::header::{ElementType, Header};
+use serde::Deserialize;
+
+/// An array or object being validated.
+struct Frame {
+    /// Offset of the header of the container, `None` for the pseudo
+    /// container that holds the root element.
+    start: Option<usize>,
+    /// Offset of the next element in the container
+    position: usize,
+    /// Offset of the end of the container
+    end: usize,
+    is_object: bool,
+    children: usize,
+}
+
+/// Validate a JSONB value, and return all the problems found in it, each
+/// with the offset of the element it was found at, in the order they
+/// appear in the input.
+///
+/// Unlike decoding, which stops at the first error, validation goes on
+/// after an error whenever it can. An element with a reserved type or an
+/// invalid payload is skipped using the size in its header. When the size
+/// itself is wrong, the elements that follow are resynchronized at the next
+/// byte that looks like the header of an element fitting in the enclosing
+/// container. This is a heuristic: an error may hide the ones that follow
+/// it, or cause spurious ones.
+///
+/// An empty list means that the value is valid.
+pub fn validate_all(bytes: &[u8]) -> Vec<(usize, Error)> {
+    validate_all_with(bytes, OnReserved::Error)
+}
+
+/// Like [`validate_all`], but treating the elements of reserved types the
+/// way [`Deserializer::with_on_reserved`](crate::Deserializer::with_on_reserved)
+/// does: they are valid with [`OnReserved::SkipAsNull`], and replaced by
+/// the value returned by the function, which is validated in turn, with
+/// [`OnReserved::Custom`]. Errors in the replacement are reported at the
+/// offset of the reserved element.
+pub fn validate_all_with(
+    bytes: &[u8],
+    on_reserved: OnReserved,
+) -> Vec<(usize, Error)> {
+    if bytes.is_empty() {
+        return vec![(0, Error::Empty)];
+    }
+    let mut errors = Vec::new();
+    let mut stack = vec![Frame {
+        start: None,
+        position: 0,
+        end: bytes.len(),
+        is_object: false,
+        children: 0,
+    }];
+    while let Some(frame) = stack.last_mut() {
+        let offset = frame.position;
+        if offset >= frame.end {
+            if frame.is_object && frame.children % 2 != 0 {
+                let start = frame.start.expect("the root is not an object");
+                errors.push((start, Error::MissingObjectValue));
+            }
+            stack.pop();
+            continue;
+        }
+        if frame.start.is_none() && frame.children == 1 {
+            errors.push((
+                offset,
+                Error::TrailingCharacters {
+                    remaining: frame.end - offset,
+                    next_element_type: ElementType::from_header_byte(
+                        bytes[offset],
+                    ),
+                },
+            ));
+            stack.pop();
+            continue;
+        }
+        let nested = frame.start.is_some();
+        let (header, header_len) =
+            match bounded_header(&bytes[offset..frame.end], nested) {
+                Ok(header) => header,
+                Err(e) => {
+                    errors.push((offset, e));
+                    frame.position =
+                        next_plausible_header(bytes, offset + 1, frame.end);
+                    continue;
+                }
+            };
+        let element_end = offset + header_len + header.payload_size;
+        frame.position = element_end;
+        frame.children += 1;
+        match header.element_type {
+            t if t.is_reserved() => match on_reserved {
+                OnReserved::Error => {
+                    errors.push((offset, Error::ReservedElementType(t)))
+                }
+                OnReserved::SkipAsNull => {}
+                OnReserved::Custom(replace) => {
+                    let payload = &bytes[offset + header_len..element_end];
+                    match replace(t, payload) {
+                        Ok(replacement) => errors.extend(
+                            validate_all(&replacement)
+                                .into_iter()
+                                .map(|(_, e)| (offset, e)),
+                        ),
+                        Err(e) => errors.push((offset, e)),
+                    }
+                }
+            },
+            t @ (ElementType::Array | ElementType::Object) => {
+                stack.push(Frame {
+                    start: Some(offset),
+                    position: offset + header_len,
+                    end: element_end,
+                    is_object: t == ElementType::Object,
+                    children: 0,
+                })
+            }
+            t => {
+                if let Err(e) = validate_scalar(t, &bytes[offset..element_end])
+                {
+                    errors.push((offset, e));
+                }
+            }
+        }
+    }
+    errors
+}
+
+/// Read the header at the start of `input`, and check that its element
+/// fits in `input`. Returns the header and its length.
+fn bounded_header(input: &[u8], nested: bool) -> Result<(Header, usize)> {
+    let (&first_byte, rest) = input.split_first().ok_or(Error::Empty)?;
+    let size_len = Header::size_field_len(first_byte);
+    if rest.len() < size_len {
+        return Err(if nested {
+            Error::ElementExceedsContainer
+        } else {
+            Error::TruncatedHeader {
+                expected: size_len,
+                got: rest.len(),
+            }
+        });
+    }
+    let header = Header::from_parts(first_byte, &rest[..size_len]);
+    let available = rest.len() - size_len;
+    if header.payload_size > available {
+        return Err(if nested {
+            Error::ElementExceedsContainer
+        } else {
+            Error::PayloadExceedsInput {
+                declared: header.payload_size,
+                available,
+            }
+        });
+    }
+    Ok((header, 1 + size_len))
+}
+
+/// The offset of the first byte, from `from`, that could be the header of
+/// an element ending before `end`, or `end` if there is none.
+fn next_plausible_header(bytes: &[u8], from: usize, end: usize) -> usize {
+    (from..end)
+        .find(|&offset| {
+            matches!(
+                bounded_header(&bytes[offset..end], true),
+                Ok((header, _)) if !header.element_type.is_reserved()
+            )
+        })
+        .unwrap_or(end)
+}
+
+/// Check the payload of a scalar element, given with its header.
+fn validate_scalar(element_type: ElementType, element: &[u8]) -> Result<()> {
+    // the JSON5 types can only be parsed with the JSON5 parser
+    if !cfg!(feature = "serde_json5")
+        && matches!(
+            element_type,
+            ElementType::Int5 | ElementType::Float5 | ElementType::Text5
+        )
+    {
+        return Ok(());
+    }
+    let mut de = crate::Deserializer::from_bytes(element)
+        .with_strict_empty_payloads(true)
+        .with_allow_non_finite(true);
+    match element_type {
+        ElementType::Null => <()>::deserialize(&mut de),
+        ElementType::True | ElementType::False => {
+            bool::deserialize(&mut de).map(drop)
+        }
+        t if t.is_text() => String::deserialize(&mut de).map(drop),
+        // integers beyond the range of an f64 keep their exactness
+        ElementType::Int | ElementType::Int5 => {
+            i128::deserialize(&mut de).map(drop).or_else(|e| {
+                let mut de = crate::Deserializer::from_bytes(element);
+                u128::deserialize(&mut de).map(drop).map_err(|_| e)
+            })
+        }
+        _ => f64::deserialize(&mut de).map(drop),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_all_valid() {
+        // {"a": [1, 2.5, "x"]}
+        let bytes = b"\xbc\x17a\x8b\x131\x352.5\x17x";
+        assert!(validate_all(bytes).is_empty());
+    }
+
+    #[test]
+    fn test_validate_all_two_malformations() {
+        // [12, <reserved>, "ab", <int that is not a number>, true]
+        let bytes = b"\xbb\x2312\x0d\x27ab\x23x1\x01";
+        let errors = validate_all(bytes);
+        assert_eq!(errors.len(), 2, "{errors:?}");
+        assert_eq!(
+            errors[0],
+            (4, Error::ReservedElementType(ElementType::Reserved13))
+        );
+        assert_eq!(errors[1].0, 8);
+    }
+
+    #[test]
+    fn test_validate_all_resynchronizes() {
+        // [[<text claiming 5 bytes> "he"], <null with a payload>, true]
+        let bytes = b"\x7b\x3b\x57he\x10\x00\x01";
+        let errors = validate_all(bytes);
+        assert_eq!(
+            errors,
+            [
+                (2, Error::ElementExceedsContainer),
+                (5, Error::UnexpectedPayload(ElementType::Null)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_all_integers() {
+        // [340282366920938463463374607431768211455, -1, 1.5, 007]
+        let bytes = b"\xcb\x34\xc3\x27340282366920938463463374607431768211455\
+            \x23-1\x331.5\x33007";
+        let errors = validate_all(bytes);
+        assert_eq!(
+            errors,
+            [
+                (46, Error::IntegerHasFraction("1.5".into())),
+                (50, Error::NonCanonicalInteger("007".into())),
+            ]
+        );
+        // one more than u128::MAX
+        let bytes = b"\xc3\x27340282366920938463463374607431768211456";
+        assert_eq!(validate_all(bytes).len(), 1);
+    }
+
+    #[test]
+    fn test_validate_all_root_errors() {
+        assert_eq!(validate_all(b""), [(0, Error::Empty)]);
+        assert_eq!(
+            validate_all(b"\x131\x00"),
+            [(
+                2,
+                Error::TrailingCharacters {
+                    remaining: 1,
+                    next_element_type: ElementType::Null,
+                }
+            )]
+        );
+        assert_eq!(
+            validate_all(b"\x57he"),
+            [(
+                0,
+                Error::PayloadExceedsInput {
+                    declared: 5,
+                    available: 2,
+                }
+            )]
+        );
+        // {"a"}
+        assert_eq!(
+            validate_all(b"\x2c\x17a"),
+            [(0, Error::MissingObjectValue)]
+        );
+    }
+
+    #[test]
+    fn test_validate_all_on_reserved() {
+        // [<reserved>, <Reserved15 with payload "x">]
+        let bytes = b"\x3b\x0d\x1fx";
+        assert_eq!(
+            validate_all(bytes),
+            [
+                (1, Error::ReservedElementType(ElementType::Reserved13)),
+                (2, Error::ReservedElementType(ElementType::Reserved15)),
+            ]
+        );
+        assert!(validate_all_with(bytes, OnReserved::SkipAsNull).is_empty());
+        let as_null = |_, _: &[u8]| Ok(b"\x00".to_vec());
+        assert!(
+            validate_all_with(bytes, OnReserved::Custom(as_null)).is_empty()
+        );
+        // the replacements are an int without its payload, and an int that
+        // is not a number
+        let as_payload =
+            |_, payload: &[u8]| Ok([&[0x13][..], payload].concat());
+        let errors = validate_all_with(bytes, OnReserved::Custom(as_payload));
+        assert_eq!(errors.len(), 2, "{errors:?}");
+        assert_eq!(
+            errors[0],
+            (
+                1,
+                Error::PayloadExceedsInput {
+                    declared: 1,
+                    available: 0,
+                }
+            )
+        );
+        assert_eq!(errors[1].0, 2);
+    }
+}
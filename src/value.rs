@@ -0,0 +1,213 @@
+//! A JSONB document written element by element, with control over the type
+//! of every element.
+
+use crate::error::{Error, Result};
+use crate::header::ElementType;
+use crate::printer::write_json_escaped;
+use crate::ser::{finalize_header, text_element_type};
+
+/// The element types that can hold a string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TextSubtype {
+    /// Text without any escape, that must not contain characters which
+    /// would need one in JSON
+    Text,
+    /// Text with JSON escapes
+    TextJ,
+    /// Text with JSON5 escapes
+    Text5,
+    /// Text without any escape, that may contain any character
+    TextRaw,
+}
+
+/// The element types that can hold a number.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NumberSubtype {
+    /// An integer in JSON syntax
+    Int,
+    /// An integer in JSON5 syntax, such as `0x1F`
+    Int5,
+    /// A float in JSON syntax
+    Float,
+    /// A float in JSON5 syntax, such as `.5` or `Infinity`
+    Float5,
+}
+
+/// A JSONB value that names the element type each of its parts is stored
+/// with, to produce blobs that use any of the encodings allowed by the
+/// format, for instance as test fixtures for decoders.
+///
+/// Values are written with [`JsonbValue::to_vec`], and not through the
+/// [`Serializer`](crate::Serializer), which picks element types itself.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JsonbValue {
+    Null,
+    Bool(bool),
+    /// A number, stored with its text as it is
+    Number {
+        subtype: NumberSubtype,
+        text: String,
+    },
+    /// A string, escaped as required by the subtype
+    Text {
+        subtype: TextSubtype,
+        value: String,
+    },
+    Array(Vec<JsonbValue>),
+    /// An object, with keys stored as `Text`, or as `TextRaw` when they
+    /// contain characters that would need escaping
+    Object(Vec<(String, JsonbValue)>),
+}
+
+impl JsonbValue {
+    /// Encode the value as JSONB.
+    ///
+    /// Fails if a `Text` subtype is requested for a string that contains
+    /// characters which would need escaping.
+    pub fn to_vec(&self) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        self.write(&mut out)?;
+        Ok(out)
+    }
+
+    fn write(&self, out: &mut Vec<u8>) -> Result<()> {
+        let header_start = out.len();
+        let element_type = match self {
+            JsonbValue::Null => ElementType::Null,
+            JsonbValue::Bool(true) => ElementType::True,
+            JsonbValue::Bool(false) => ElementType::False,
+            JsonbValue::Number { subtype, .. } => match subtype {
+                NumberSubtype::Int => ElementType::Int,
+                NumberSubtype::Int5 => ElementType::Int5,
+                NumberSubtype::Float => ElementType::Float,
+                NumberSubtype::Float5 => ElementType::Float5,
+            },
+            JsonbValue::Text { subtype, .. } => match subtype {
+                TextSubtype::Text => ElementType::Text,
+                TextSubtype::TextJ => ElementType::TextJ,
+                TextSubtype::Text5 => ElementType::Text5,
+                TextSubtype::TextRaw => ElementType::TextRaw,
+            },
+            JsonbValue::Array(_) => ElementType::Array,
+            JsonbValue::Object(_) => ElementType::Object,
+        };
+        // room for the largest header, shrunk by finalize_header
+        out.extend_from_slice(&[u8::from(element_type); 9]);
+        match self {
+            JsonbValue::Null | JsonbValue::Bool(_) => {}
+            JsonbValue::Number { text, .. } => {
+                out.extend_from_slice(text.as_bytes())
+            }
+            JsonbValue::Text { subtype, value } => match subtype {
+                TextSubtype::Text
+                    if text_element_type(value) != ElementType::Text =>
+                {
+                    return Err(Error::Message(format!(
+                        "{value:?} cannot be stored as Text without escapes"
+                    )));
+                }
+                TextSubtype::Text | TextSubtype::TextRaw => {
+                    out.extend_from_slice(value.as_bytes())
+                }
+                TextSubtype::TextJ | TextSubtype::Text5 => {
+                    let mut escaped = String::with_capacity(value.len());
+                    write_json_escaped(value, &mut escaped);
+                    out.extend_from_slice(escaped.as_bytes());
+                }
+            },
+            JsonbValue::Array(elements) => {
+                for element in elements {
+                    element.write(out)?;
+                }
+            }
+            JsonbValue::Object(entries) => {
+                for (key, value) in entries {
+                    JsonbValue::Text {
+                        subtype: match text_element_type(key) {
+                            ElementType::Text => TextSubtype::Text,
+                            _ => TextSubtype::TextRaw,
+                        },
+                        value: key.clone(),
+                    }
+                    .write(out)?;
+                    value.write(out)?;
+                }
+            }
+        }
+        finalize_header(out, header_start);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(subtype: TextSubtype, value: &str) -> JsonbValue {
+        JsonbValue::Text {
+            subtype,
+            value: value.into(),
+        }
+    }
+
+    fn number(subtype: NumberSubtype, text: &str) -> JsonbValue {
+        JsonbValue::Number {
+            subtype,
+            text: text.into(),
+        }
+    }
+
+    #[test]
+    fn test_text_subtypes() {
+        let cases = [
+            (TextSubtype::Text, "ab", &b"\x27ab"[..]),
+            (TextSubtype::TextRaw, "a\"b", b"\x3aa\"b"),
+            (TextSubtype::TextJ, "a\nb", b"\x48a\\nb"),
+            (TextSubtype::Text5, "a\"b", b"\x49a\\\"b"),
+        ];
+        for (subtype, value, expected) in cases {
+            let encoded = text(subtype, value).to_vec().unwrap();
+            assert_eq!(encoded, expected);
+            #[cfg(not(feature = "serde_json5"))]
+            if subtype == TextSubtype::Text5 {
+                continue;
+            }
+            assert_eq!(crate::from_slice::<String>(&encoded).unwrap(), value);
+        }
+        assert!(text(TextSubtype::Text, "a\nb").to_vec().is_err());
+    }
+
+    #[test]
+    fn test_number_subtypes() {
+        let cases = [
+            (NumberSubtype::Int, "12", &b"\x2312"[..]),
+            (NumberSubtype::Int5, "0x1F", b"\x440x1F"),
+            (NumberSubtype::Float, "1.5", b"\x351.5"),
+            (NumberSubtype::Float5, ".5", b"\x26.5"),
+        ];
+        for (subtype, text, expected) in cases {
+            assert_eq!(number(subtype, text).to_vec().unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn test_nested_value() {
+        let value = JsonbValue::Object(vec![
+            (
+                "a".into(),
+                JsonbValue::Array(vec![
+                    JsonbValue::Null,
+                    JsonbValue::Bool(true),
+                    number(NumberSubtype::Int, "1"),
+                ]),
+            ),
+            ("b\"".into(), text(TextSubtype::TextJ, "x")),
+        ]);
+        let encoded = value.to_vec().unwrap();
+        assert_eq!(encoded, b"\xcc\x0c\x17a\x4b\x00\x01\x131\x2ab\"\x18x");
+        assert_eq!(
+            crate::to_json_string(&encoded).unwrap(),
+            r#"{"a":[null,true,1],"b\"":"x"}"#
+        );
+    }
+}
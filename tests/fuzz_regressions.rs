@@ -0,0 +1,68 @@
+//! Inputs found by the `roundtrip` fuzz target in `fuzz/`.
+//! Each one must decode without panicking, and if it decodes, it must
+//! survive an encode and decode roundtrip unchanged.
+#![cfg(feature = "serde_json")]
+
+use serde::Deserialize;
+use serde_json::Value;
+use serde_sqlite_jsonb::Deserializer;
+
+/// The nesting limit of the fuzz target, which keeps deeply nested input
+/// from overflowing the stack.
+const MAX_DEPTH: usize = 128;
+
+fn check_roundtrip(data: &[u8]) {
+    let mut de = Deserializer::from_bytes(data).with_max_depth(MAX_DEPTH);
+    let value = match Value::deserialize(&mut de).and_then(|v| {
+        de.end()?;
+        Ok(v)
+    }) {
+        Ok(value) => value,
+        Err(_) => return,
+    };
+    let encoded = serde_sqlite_jsonb::to_vec(&value)
+        .expect("a decoded value can be encoded");
+    let decoded: Value = serde_sqlite_jsonb::from_slice(&encoded)
+        .expect("an encoded value can be decoded");
+    assert_eq!(value, decoded, "roundtrip of {data:?}");
+}
+
+#[test]
+fn test_fuzz_regressions() {
+    for data in [
+        &b""[..],
+        b"\x0d",
+        b"\xff",
+        b"\xf3\xff\xff\xff\xff\xff\xff\xff\xff",
+        b"\x1b\x0b",
+        b"\x2c\x17a",
+        b"\x35nan",
+        b"\x551e999",
+        b"\x23-0",
+        b"\x5b\xc3\x00\x00\x00",
+        b"\x1a\xff",
+        b"\x28\\ud800",
+        b"\x2c\x00\x00",
+        b"\x4c\x131\x132",
+    ] {
+        check_roundtrip(data);
+    }
+}
+
+/// `depth` arrays nested in each other
+fn nested_arrays(depth: usize) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(9 * depth);
+    for level in (1..depth).rev() {
+        // each level has a nine-byte header, the innermost array has one
+        let payload_size = 9 * level as u64 - 8;
+        bytes.push(0xfb);
+        bytes.extend_from_slice(&payload_size.to_be_bytes());
+    }
+    bytes.push(0x0b);
+    bytes
+}
+
+#[test]
+fn test_deeply_nested_arrays() {
+    check_roundtrip(&nested_arrays(1000));
+}
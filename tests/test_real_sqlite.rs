@@ -131,3 +131,123 @@ fn test_roadtrip() {
         serde_sqlite_jsonb::from_slice(&went_through).unwrap();
     assert_eq!(my_obj, decoded, "went through sqlite and back");
 }
+
+#[test]
+fn test_array_with_nulls() -> rusqlite::Result<()> {
+    let conn = Connection::open_in_memory()?;
+    let blob: Vec<u8> =
+        conn.query_row("select jsonb('[1, null, 3]')", [], |row| row.get(0))?;
+    let decoded: Vec<Option<i32>> =
+        serde_sqlite_jsonb::from_slice(&blob).unwrap();
+    assert_eq!(decoded, vec![Some(1), None, Some(3)]);
+    // the same, from a stream: each header must be read exactly once
+    let decoded: Vec<Option<i32>> =
+        serde_sqlite_jsonb::from_reader(&blob[..]).unwrap();
+    assert_eq!(decoded, vec![Some(1), None, Some(3)]);
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type")]
+enum Shape {
+    Circle { radius: f64 },
+    Square { name: String, side: u32 },
+    Empty,
+}
+
+#[test]
+fn test_internally_tagged_enum() -> rusqlite::Result<()> {
+    let conn = Connection::open_in_memory()?;
+    // the tag is not necessarily the first key of the object
+    let blob: Vec<u8> = conn.query_row(
+        r#"select jsonb('[
+        {"type": "Circle", "radius": 1.5},
+        {"name": "sq", "side": 2, "type": "Square"},
+        {"type": "Empty", "ignored": [null]}
+    ]')"#,
+        [],
+        |row| row.get(0),
+    )?;
+    let expected = vec![
+        Shape::Circle { radius: 1.5 },
+        Shape::Square {
+            name: "sq".to_string(),
+            side: 2,
+        },
+        Shape::Empty,
+    ];
+    let decoded: Vec<Shape> = serde_sqlite_jsonb::from_slice(&blob).unwrap();
+    assert_eq!(decoded, expected);
+    let decoded: Vec<Shape> =
+        serde_sqlite_jsonb::from_reader(&blob[..]).unwrap();
+    assert_eq!(decoded, expected);
+    let encoded = serde_sqlite_jsonb::to_vec(&expected).unwrap();
+    let decoded: Vec<Shape> = serde_sqlite_jsonb::from_slice(&encoded).unwrap();
+    assert_eq!(decoded, expected);
+    Ok(())
+}
+
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "t", content = "c")]
+enum Command {
+    Move { x: i32, y: i32 },
+    Say(String),
+    Stop,
+}
+
+#[test]
+fn test_adjacently_tagged_enum() -> rusqlite::Result<()> {
+    let conn = Connection::open_in_memory()?;
+    // the content may come before the tag, which serde then buffers
+    let blob: Vec<u8> = conn.query_row(
+        r#"select jsonb('[
+        {"t": "Move", "c": {"x": 1, "y": -2}},
+        {"c": {"y": 4, "x": 3}, "t": "Move"},
+        {"t": "Say", "c": "hi"},
+        {"c": "bye", "t": "Say"},
+        {"t": "Stop"}
+    ]')"#,
+        [],
+        |row| row.get(0),
+    )?;
+    let expected = vec![
+        Command::Move { x: 1, y: -2 },
+        Command::Move { x: 3, y: 4 },
+        Command::Say("hi".to_string()),
+        Command::Say("bye".to_string()),
+        Command::Stop,
+    ];
+    let decoded: Vec<Command> = serde_sqlite_jsonb::from_slice(&blob).unwrap();
+    assert_eq!(decoded, expected);
+    let decoded: Vec<Command> =
+        serde_sqlite_jsonb::from_reader(&blob[..]).unwrap();
+    assert_eq!(decoded, expected);
+    let encoded = serde_sqlite_jsonb::to_vec(&expected).unwrap();
+    let decoded: Vec<Command> =
+        serde_sqlite_jsonb::from_slice(&encoded).unwrap();
+    assert_eq!(decoded, expected);
+    Ok(())
+}
+
+#[test]
+fn test_root_array_into_tuple() -> rusqlite::Result<()> {
+    let conn = Connection::open_in_memory()?;
+    let blob: Vec<u8> =
+        conn.query_row(r#"select jsonb('[1, "x", true]')"#, [], |row| {
+            row.get(0)
+        })?;
+    let expected = (1, "x".to_string(), true);
+    let decoded: (u8, String, bool) =
+        serde_sqlite_jsonb::from_slice(&blob).unwrap();
+    assert_eq!(decoded, expected);
+    let decoded: (u8, String, bool) =
+        serde_sqlite_jsonb::from_reader(&blob[..]).unwrap();
+    assert_eq!(decoded, expected);
+    // the length of the tuple is enforced both ways
+    assert!(serde_sqlite_jsonb::from_slice::<(u8, String)>(&blob).is_err());
+    assert!(
+        serde_sqlite_jsonb::from_slice::<(u8, String, bool, ())>(&blob)
+            .is_err()
+    );
+    Ok(())
+}
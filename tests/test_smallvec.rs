@@ -0,0 +1,37 @@
+//! Decoding of small arrays into `SmallVec`s, which keep up to `N` elements
+//! inline if the deserializer's size hint does not make them reserve more.
+#![cfg(feature = "smallvec")]
+
+use serde_sqlite_jsonb::from_slice;
+use smallvec::SmallVec;
+
+#[test]
+fn test_small_array_stays_inline() {
+    // [1, 2, 3]
+    let v: SmallVec<[i64; 4]> = from_slice(b"\x6b\x131\x132\x133").unwrap();
+    assert_eq!(v.as_slice(), [1, 2, 3]);
+    assert!(!v.spilled());
+    let v: SmallVec<[i64; 4]> = from_slice(b"\x0b").unwrap();
+    assert!(v.is_empty());
+    assert!(!v.spilled());
+}
+
+#[test]
+fn test_large_array_spills() {
+    // [1, 2, 3, 4, 5]
+    let bytes = b"\xab\x131\x132\x133\x134\x135";
+    let v: SmallVec<[u8; 2]> = from_slice(bytes).unwrap();
+    assert_eq!(v.as_slice(), [1, 2, 3, 4, 5]);
+    assert!(v.spilled());
+}
+
+#[test]
+fn test_nested_small_arrays() {
+    // [["a", "b"], []]
+    let bytes = b"\x6b\x4b\x17a\x17b\x0b";
+    let v: Vec<SmallVec<[String; 2]>> = from_slice(bytes).unwrap();
+    assert_eq!(v.len(), 2);
+    assert_eq!(v[0].as_slice(), ["a", "b"]);
+    assert!(!v[0].spilled());
+    assert!(v[1].is_empty());
+}
@@ -0,0 +1,55 @@
+//! Smart pointers are decoded by forwarding to the type they point to.
+
+use serde_derive::Deserialize;
+use serde_sqlite_jsonb::{from_reader, from_slice};
+use std::rc::Rc;
+use std::sync::Arc;
+
+#[derive(Debug, PartialEq, Deserialize)]
+struct Point {
+    x: i64,
+    label: Option<String>,
+}
+
+// {"x": 3, "label": "a"}
+const POINT: &[u8] = b"\xcc\x0c\x17x\x133\x57label\x17a";
+
+#[test]
+fn test_box() {
+    let expected = Point {
+        x: 3,
+        label: Some("a".into()),
+    };
+    assert_eq!(*from_slice::<Box<Point>>(POINT).unwrap(), expected);
+    assert_eq!(*from_reader::<_, Box<Point>>(POINT).unwrap(), expected);
+    let boxed: Box<str> = from_slice(b"\x27hi").unwrap();
+    assert_eq!(&*boxed, "hi");
+}
+
+#[test]
+fn test_rc() {
+    assert_eq!(*from_slice::<Rc<String>>(b"\x27hi").unwrap(), "hi");
+    let shared: Rc<Point> = from_slice(POINT).unwrap();
+    assert_eq!(shared.x, 3);
+}
+
+#[test]
+fn test_arc() {
+    // [1, -2, 3]
+    let bytes = b"\x7b\x131\x23-2\x133";
+    assert_eq!(*from_slice::<Arc<Vec<i64>>>(bytes).unwrap(), [1, -2, 3]);
+    let slice: Arc<[i64]> = from_slice(bytes).unwrap();
+    assert_eq!(&*slice, [1, -2, 3]);
+}
+
+#[test]
+fn test_nested_pointers() {
+    #[derive(Debug, Deserialize)]
+    struct Node {
+        next: Option<Box<Node>>,
+    }
+    // {"next": {"next": null}}
+    let bytes = b"\xcc\x0c\x4anext\x6c\x4anext\x00";
+    let node: Node = from_slice(bytes).unwrap();
+    assert!(node.next.unwrap().next.is_none());
+}